@@ -1,37 +1,235 @@
-use std::path::PathBuf;
+use std::{collections::HashSet, path::PathBuf};
 
-use clap::{Parser, Subcommand};
+use anyhow::bail;
+use clap::{parser::ValueSource, CommandFactory, FromArgMatches, Parser, Subcommand};
+use serde::{Deserialize, Serialize};
 
 mod split;
 
+use split::{
+    config::ConfigFormat,
+    guests::GuestPolicy,
+    history::DeletedPolicy,
+    inspect::OutputFormat,
+    subtypes::{SubtypePolicy, SubtypeRule},
+    teams::{TeamRule, Teams},
+    users::DeletedUsersPolicy,
+};
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     #[command(subcommand)]
     cmd: Commands,
 
-    #[arg(long, default_value_t = 100)]
-    chunk_size: usize,
+    /// TOML or YAML file with options named like the flags, `SMS_*` variables
+    /// and flags override it
+    #[arg(long, env = "SMS_CONFIG")]
+    config: Option<PathBuf>,
+
+    #[command(flatten)]
+    options: Options,
+}
+
+#[derive(clap::Args, Debug, Clone, Serialize, Deserialize)]
+struct Options {
+    /// Maximum conversations per chunk, more chunks are made when exceeded,
+    /// not limited by default
+    #[arg(long, env = "SMS_CHUNK_SIZE")]
+    chunk_size: Option<usize>,
 
-    #[arg(long, default_value_t = 1)]
+    #[arg(long, default_value_t = 1, env = "SMS_NUM_CHUNKS")]
     num_chunks: usize,
 
-    #[arg(long, default_value_t = 5)]
+    #[arg(long, default_value_t = 5, env = "SMS_CONCURRENT")]
     concurrent: usize,
 
-    #[arg(long, default_value_t = false)]
+    #[arg(long, default_value_t = false, env = "SMS_SKIP_DOWNLOADING")]
     skip_downloading: bool,
 
-    #[arg(long, default_value_t = false)]
+    #[arg(long, default_value_t = false, env = "SMS_SKIP_DIRECTS")]
     skip_directs: bool,
 
-    #[arg(long, default_value_t = false)]
+    #[arg(long, default_value_t = false, env = "SMS_SKIP_CHANNELS")]
     skip_channels: bool,
+
+    #[arg(long, default_value_t = false, env = "SMS_SKIP_AVATARS")]
+    skip_avatars: bool,
+
+    #[arg(long, default_value_t = false, env = "SMS_SKIP_ARCHIVED")]
+    skip_archived: bool,
+
+    #[arg(long, env = "SMS_EMOJI_FILE")]
+    emoji_file: Option<PathBuf>,
+
+    #[arg(long, env = "SMS_USER_MAP")]
+    user_map: Option<PathBuf>,
+
+    #[arg(long, env = "SMS_BOT_USER")]
+    bot_user: Option<String>,
+
+    /// Map all external (Slack Connect) authors to one account
+    #[arg(long, env = "SMS_EXTERNAL_USER")]
+    external_user: Option<String>,
+
+    /// Post subtype policy as subtype=drop|keep|update, may be repeated
+    #[arg(long, env = "SMS_SUBTYPES", value_delimiter = ',')]
+    subtype: Vec<SubtypeRule>,
+
+    #[arg(long, default_value_t = false, env = "SMS_PRESERVE_EDITS")]
+    preserve_edits: bool,
+
+    /// Deleted messages policy: drop or tombstone
+    #[arg(long, default_value = "drop", env = "SMS_DELETED_MESSAGES")]
+    deleted_messages: DeletedPolicy,
+
+    /// Deleted users policy: import, map (to --former-user) or skip (their DMs,
+    /// group DMs and channel memberships)
+    #[arg(long, default_value = "import", env = "SMS_DELETED_USERS")]
+    deleted_users: DeletedUsersPolicy,
+
+    #[arg(long, default_value = "former.employee", env = "SMS_FORMER_USER")]
+    former_user: String,
+
+    /// Restricted Slack accounts policy: guest or member
+    #[arg(long, default_value = "guest", env = "SMS_GUEST_POLICY")]
+    guest_policy: GuestPolicy,
+
+    /// Channel guests are allowed to join, workspace/name limits it to one
+    /// Enterprise Grid workspace, may be repeated
+    #[arg(long, env = "SMS_GUEST_CHANNELS", value_delimiter = ',')]
+    guest_channel: Vec<String>,
+
+    /// Mattermost team of the generated import scripts as team or
+    /// workspace=team for Enterprise Grid workspaces, may be repeated
+    #[arg(long, env = "SMS_TEAM", value_delimiter = ',')]
+    team: Vec<TeamRule>,
 }
 
 #[derive(Subcommand, Debug, Clone)]
 enum Commands {
-    Transform { input: String, output: String },
+    Transform {
+        input: String,
+        output: String,
+        /// Reproduce chunk layout from `plan` output
+        #[arg(long)]
+        plan: Option<PathBuf>,
+        /// Export only posts newer than `state.json` of a previous run. Later
+        /// edits and deletions of posts exported before are not carried, run a
+        /// full export to pick them up
+        #[arg(long)]
+        since: Option<PathBuf>,
+    },
+    /// Check the export for structural and referential problems
+    Validate {
+        input: String,
+        /// Output format: table or json
+        #[arg(long, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Check generated chunks against the source archive
+    Verify {
+        input: String,
+        output: String,
+        /// Output format: table or json
+        #[arg(long, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Print chunk layout as JSON without writing archives
+    Plan {
+        input: String,
+        /// Write the plan to file instead of stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Print channels statistics of the archive
+    Inspect {
+        input: String,
+        /// Output format: table or json
+        #[arg(long, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Combine several exports into one, later exports win on conflicts
+    Merge {
+        output: PathBuf,
+        #[arg(required = true, num_args = 1..)]
+        inputs: Vec<PathBuf>,
+        /// Output format of conflicts: table or json
+        #[arg(long, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Export only the given conversations as one importable archive
+    Extract {
+        input: String,
+        output: String,
+        /// Channel name or id, workspace/name in Enterprise Grid exports, may be repeated
+        #[arg(long = "channel")]
+        channels: Vec<String>,
+        /// Comma separated members of a direct or group conversation, may be repeated
+        #[arg(long = "dm")]
+        dms: Vec<String>,
+    },
+    /// Render conversations as HTML transcripts into `output/transcripts`
+    ///
+    /// Deleted messages are shown as tombstones and edits are marked unless
+    /// --deleted-messages or --preserve-edits is set
+    Transcript {
+        input: String,
+        output: String,
+        /// Time zone of message timestamps, like Europe/Berlin
+        #[arg(long, default_value = "UTC")]
+        timezone: chrono_tz::Tz,
+        /// Write plain text transcripts as well
+        #[arg(long, default_value_t = false)]
+        text: bool,
+    },
+    /// Inspect the effective configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum ConfigCommand {
+    /// Print options merged from the config file, environment and flags
+    Dump {
+        /// Output format: toml, yaml or json
+        #[arg(long, default_value = "toml")]
+        format: ConfigFormat,
+    },
+}
+
+impl Args {
+    fn config(self, input: String, output: String) -> split::Config {
+        let options = self.options;
+        split::Config {
+            slack_archive: PathBuf::from(input),
+            output: PathBuf::from(output),
+            plan: None,
+            since: None,
+            teams: Teams::new(&options.team),
+            chunk_size: options.chunk_size,
+            num_chunks: options.num_chunks,
+            concurrent: options.concurrent,
+            skip_directs: options.skip_directs,
+            skip_channels: options.skip_channels,
+            skip_downloading: options.skip_downloading,
+            skip_avatars: options.skip_avatars,
+            skip_archived: options.skip_archived,
+            emoji_file: options.emoji_file,
+            user_map: options.user_map,
+            bot_user: options.bot_user,
+            external_user: options.external_user,
+            subtype_policy: SubtypePolicy::new(&options.subtype),
+            preserve_edits: options.preserve_edits,
+            deleted_messages: options.deleted_messages,
+            deleted_users: options.deleted_users,
+            former_user: options.former_user,
+            guest_policy: options.guest_policy,
+            guest_channels: options.guest_channel,
+        }
+    }
 }
 
 #[tokio::main]
@@ -44,22 +242,164 @@ async fn main() -> anyhow::Result<()> {
 
     env_logger::init();
 
-    let args = Args::parse();
-    match args.cmd {
-        Commands::Transform { input, output } => {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches)?;
+    // Config file < SMS_* environment variables < flags
+    let is_explicit = |key: &str| {
+        matches!(
+            matches.value_source(key),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        )
+    };
+    let mut file_keys = HashSet::new();
+    if let Some(path) = args.config.clone() {
+        let file = split::config::read_file(&path)?;
+        file_keys = file.keys().map(|k| k.replace('-', "_")).collect();
+        args.options = split::config::layer(&args.options, file, is_explicit)?;
+    }
+    let is_set = |key: &str| is_explicit(key) || file_keys.contains(key);
+    match args.cmd.clone() {
+        Commands::Transform {
+            input,
+            output,
+            plan,
+            since,
+        } => {
             let config = split::Config {
-                slack_archive: PathBuf::from(input),
-                output: PathBuf::from(output),
-                chunk_size: args.chunk_size,
-                num_chunks: args.num_chunks,
-                concurrent: args.concurrent,
-                skip_directs: args.skip_directs,
-                skip_channels: args.skip_channels,
-                skip_downloading: args.skip_downloading,
+                plan,
+                since,
+                ..args.config(input, output)
             };
             let mut splitter = split::Splitter::new(config).await?;
             splitter.split().await?;
         }
+        Commands::Inspect { input, format } => {
+            let config = args.config(input, String::new());
+            let mut splitter = split::Splitter::new(config).await?;
+            let report = splitter.inspect().await?;
+            println!("{}", report.render(format)?);
+        }
+        Commands::Validate { input, format } => {
+            let config = args.config(input, String::new());
+            let mut splitter = split::Splitter::new(config).await?;
+            let mut problems = splitter.validate().await?;
+            println!("{}", problems.render(format)?);
+            if problems.errors() > 0 {
+                bail!("Validation failed with {} errors", problems.errors());
+            }
+        }
+        Commands::Verify {
+            input,
+            output,
+            format,
+        } => {
+            let config = args.config(input, output);
+            let mut splitter = split::Splitter::new(config).await?;
+            let mut problems = splitter.verify().await?;
+            println!("{}", problems.render(format)?);
+            if problems.errors() > 0 {
+                bail!("Verification failed with {} errors", problems.errors());
+            }
+        }
+        Commands::Plan { input, out } => {
+            let config = args.config(input, String::new());
+            let mut splitter = split::Splitter::new(config).await?;
+            let plan = serde_json::to_string_pretty(&splitter.plan().await?)?;
+            match out {
+                Some(path) => std::fs::write(path, plan)?,
+                None => println!("{}", plan),
+            }
+        }
+        Commands::Extract {
+            input,
+            output,
+            channels,
+            dms,
+        } => {
+            if channels.is_empty() && dms.is_empty() {
+                bail!("Nothing to extract, use --channel or --dm");
+            }
+            let config = args.config(input, output);
+            let mut splitter = split::Splitter::new(config).await?;
+            splitter.extract(&channels, &dms).await?;
+        }
+        Commands::Transcript {
+            input,
+            output,
+            timezone,
+            text,
+        } => {
+            let mut config = args.config(input, output);
+            // Transcripts keep deleted and edited messages unless told otherwise
+            if !is_set("deleted_messages") {
+                config.deleted_messages = DeletedPolicy::Tombstone;
+            }
+            if !is_set("preserve_edits") {
+                config.preserve_edits = true;
+            }
+            let mut splitter = split::Splitter::new(config).await?;
+            splitter.transcript(timezone, text).await?;
+        }
+        Commands::Config {
+            action: ConfigCommand::Dump { format },
+        } => {
+            print!("{}", format.render(&args.options)?);
+        }
+        Commands::Merge {
+            output,
+            inputs,
+            format,
+        } => {
+            let mut merger = split::merge::Merger::new(inputs).await?;
+            merger.merge(output).await?;
+            println!("{}", merger.problems.render(format)?);
+            if merger.problems.errors() > 0 {
+                bail!("Merge finished with {} errors", merger.problems.errors());
+            }
+        }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_dump_roundtrip() {
+        let args = Args::try_parse_from([
+            "sms",
+            "--num-chunks=4",
+            "--subtype=channel_join=drop,channel_topic=update",
+            "--team=acme,sales=acme-sales",
+            "--guest-channel=acme/general",
+            "config",
+            "dump",
+        ])
+        .unwrap();
+        let dump = ConfigFormat::Toml.render(&args.options).unwrap();
+        assert!(dump.contains("subtype = [\n    \"channel_join=drop\","));
+        assert!(dump.contains("\"sales=acme-sales\""));
+        let serde_json::Value::Object(file) = toml::from_str(&dump).unwrap() else {
+            panic!("not a table");
+        };
+
+        // Flags win over the file
+        let matches = Args::command()
+            .try_get_matches_from(["sms", "--num-chunks", "8", "config", "dump"])
+            .unwrap();
+        let cli = Args::from_arg_matches(&matches).unwrap();
+        let options = split::config::layer(&cli.options, file.clone(), |key| {
+            matches.value_source(key) == Some(ValueSource::CommandLine)
+        })
+        .unwrap();
+        assert_eq!(options.num_chunks, 8);
+        assert_eq!(options.subtype, args.options.subtype);
+        assert_eq!(options.team, args.options.team);
+        assert_eq!(options.guest_channel, vec!["acme/general"]);
+
+        let mut unknown = file;
+        unknown.insert("subtypes".to_string(), serde_json::Value::from(1));
+        assert!(split::config::layer(&cli.options, unknown, |_| false).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use serde_json::json;
+
+use super::model::SlackPost;
+
+pub fn is_bot_message(post: &SlackPost) -> bool {
+    post.str_field("subtype") == Some("bot_message")
+        || (post.user().is_none() && post.extra.get("bot_id").is_some())
+}
+
+// Attach bot messages to `bot_user` when configured and keep the original
+// bot name and icon as Mattermost override props
+pub fn convert_bot_message(post: &mut SlackPost, bot_user: Option<&str>) {
+    let bot_profile = post.extra.get("bot_profile").cloned().unwrap_or_default();
+    let username = post
+        .str_field("username")
+        .or_else(|| bot_profile.get("name").and_then(|n| n.as_str()))
+        .map(|n| n.to_string());
+    let icons = post
+        .extra
+        .get("icons")
+        .or_else(|| bot_profile.get("icons"))
+        .cloned()
+        .unwrap_or_default();
+    let icon_url = ["image_72", "image_48", "image_36", "image_64"]
+        .iter()
+        .find_map(|k| icons.get(*k).and_then(|i| i.as_str()))
+        .map(|i| i.to_string());
+
+    let mut props = json!({"from_bot": "true"});
+    if let Some(username) = username {
+        props["override_username"] = json!(username);
+    }
+    if let Some(icon_url) = icon_url {
+        props["override_icon_url"] = json!(icon_url);
+    }
+    post.merge_props(props);
+
+    if let Some(bot_user) = bot_user {
+        post.set("user", bot_user);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split::render;
+
+    #[test]
+    fn test_convert_bot_message() {
+        let mut post: SlackPost = serde_json::from_value(json!({
+            "type": "message",
+            "subtype": "bot_message",
+            "bot_id": "B1",
+            "username": "deploybot",
+            "icons": {"image_48": "https://example.com/bot.png"},
+            "text": "",
+            "attachments": [{"fallback": "Deployed", "color": "good"}]
+        }))
+        .unwrap();
+
+        assert!(is_bot_message(&post));
+        convert_bot_message(&mut post, Some("U0BOT"));
+        render::render_post(&mut post, true);
+
+        assert_eq!(post.user(), Some("U0BOT"));
+        assert_eq!(post.text(), Some(""));
+        assert_eq!(post.extra["props"]["override_username"], json!("deploybot"));
+        assert_eq!(
+            post.extra["props"]["override_icon_url"],
+            json!("https://example.com/bot.png")
+        );
+        assert_eq!(
+            post.extra["props"]["attachments"][0]["color"],
+            json!("good")
+        );
+    }
+}
@@ -1,14 +1,129 @@
-use std::path::PathBuf;
+use std::{path::Path, path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, bail, Error, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::{
+    guests::GuestPolicy, history::DeletedPolicy, subtypes::SubtypePolicy, teams::Teams,
+    users::DeletedUsersPolicy,
+};
 
 #[derive(Debug)]
 pub struct Config {
     pub slack_archive: PathBuf,
     pub output: PathBuf,
-    pub chunk_size: usize,
+    // Chunk layout from `plan` output
+    pub plan: Option<PathBuf>,
+    // `state.json` of a previous run, only newer posts are exported
+    pub since: Option<PathBuf>,
+    // Mattermost team of each workspace for generated import scripts
+    pub teams: Teams,
+    // Maximum conversations per chunk, on top of `num_chunks`
+    pub chunk_size: Option<usize>,
     pub num_chunks: usize,
     pub concurrent: usize,
 
     pub skip_downloading: bool,
     pub skip_directs: bool,
     pub skip_channels: bool,
+    pub skip_avatars: bool,
+    pub skip_archived: bool,
+
+    // Custom emoji listing, `emoji.json` from the archive is used by default
+    pub emoji_file: Option<PathBuf>,
+    // Slack user id or email to existing Mattermost username (CSV or JSON)
+    pub user_map: Option<PathBuf>,
+    // Slack user id or name to post bot messages as
+    pub bot_user: Option<String>,
+    // Mattermost username for all Slack Connect authors missing from users.json,
+    // placeholder users are created when not set
+    pub external_user: Option<String>,
+    // What to do with system posts (joins, leaves, topic changes, ...)
+    pub subtype_policy: SubtypePolicy,
+    // Keep Slack `edited` metadata as Mattermost `edit_at`
+    pub preserve_edits: bool,
+    pub deleted_messages: DeletedPolicy,
+    pub deleted_users: DeletedUsersPolicy,
+    // Mattermost username of the account for deleted users with `map` policy
+    pub former_user: String,
+    pub guest_policy: GuestPolicy,
+    // Channels guests may be members of, any channel when empty
+    pub guest_channels: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    #[default]
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl FromStr for ConfigFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "toml" => Ok(ConfigFormat::Toml),
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            "json" => Ok(ConfigFormat::Json),
+            _ => bail!("Unknown config format {}, expected toml, yaml or json", s),
+        }
+    }
+}
+
+impl ConfigFormat {
+    pub fn render<T: Serialize>(&self, value: &T) -> Result<String> {
+        match self {
+            ConfigFormat::Toml => Ok(toml::to_string_pretty(value)?),
+            ConfigFormat::Yaml => Ok(serde_yaml::to_string(value)?),
+            ConfigFormat::Json => Ok(serde_json::to_string_pretty(value)?),
+        }
+    }
+}
+
+// Options from a config file, the format is chosen by file extension
+pub fn read_file(path: &Path) -> Result<Map<String, Value>> {
+    let format: ConfigFormat = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .parse()?;
+    let data = std::fs::read_to_string(path)?;
+    let value: Value = match format {
+        ConfigFormat::Toml => toml::from_str(&data).map_err(Error::from),
+        ConfigFormat::Yaml => serde_yaml::from_str(&data).map_err(Error::from),
+        ConfigFormat::Json => serde_json::from_str(&data).map_err(Error::from),
+    }
+    .map_err(|e| anyhow!("Failed to read config {:?}: {}", path, e))?;
+    match value {
+        Value::Object(options) => Ok(options),
+        _ => bail!("Config {:?} is not a table of options", path),
+    }
+}
+
+// Put file values under `options` unless `is_explicit` tells the option was
+// set by a flag or an environment variable
+pub fn layer<T>(
+    options: &T,
+    file: Map<String, Value>,
+    is_explicit: impl Fn(&str) -> bool,
+) -> Result<T>
+where
+    T: Serialize + serde::de::DeserializeOwned,
+{
+    let Value::Object(mut values) = serde_json::to_value(options)? else {
+        bail!("Options are not a table");
+    };
+    for (key, value) in file {
+        let key = key.replace('-', "_");
+        if !values.contains_key(&key) {
+            bail!("Unknown option {} in config file", key);
+        }
+        if !is_explicit(&key) {
+            values.insert(key, value);
+        }
+    }
+    serde_json::from_value(Value::Object(values)).map_err(|e| anyhow!("Invalid config: {}", e))
 }
@@ -0,0 +1,134 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{grid::dir_of, merge::ts_key, model::SlackPost};
+
+// State of a transform run, written as `state.json` to the output directory.
+// `transform --since` exports only what is newer than the previous state.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct State {
+    pub archive: String,
+    pub users: BTreeSet<String>,
+    // Ids of channels, groups, mpims and directs
+    pub channels: BTreeSet<String>,
+    // Last exported day and post by conversation directory
+    pub conversations: BTreeMap<String, Progress>,
+    // Progress of the previous run with `--since`, only newer posts are in
+    // the chunks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<BTreeMap<String, Progress>>,
+    // Chunk layout file with `--plan`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Progress {
+    pub last_day: String,
+    pub last_ts: String,
+}
+
+impl State {
+    // State to filter with, as the previous run of an incremental one
+    pub fn previous(&self) -> Option<State> {
+        self.since.as_ref().map(|conversations| State {
+            archive: self.archive.clone(),
+            conversations: conversations.clone(),
+            ..Default::default()
+        })
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)?;
+        serde_json::from_slice(&data).map_err(|e| anyhow!("Failed to read state {:?}: {}", path, e))
+    }
+
+    // Record the latest day and post of a day file
+    pub fn add_day(&mut self, filename: &str, posts: &[SlackPost]) {
+        let progress = self
+            .conversations
+            .entry(dir_of(filename).to_string())
+            .or_default();
+        let day = day_of(filename);
+        if day > progress.last_day.as_str() {
+            progress.last_day = day.to_string();
+        }
+        for ts in posts.iter().filter_map(|p| p.str_field("ts")) {
+            if ts_key(ts) > ts_key(&progress.last_ts) {
+                progress.last_ts = ts.to_string();
+            }
+        }
+    }
+
+    // Day file may contain posts missing from the previous run,
+    // earlier days of known conversations are skipped
+    pub fn is_new_day(&self, filename: &str) -> bool {
+        match self.conversations.get(dir_of(filename)) {
+            Some(progress) => day_of(filename) >= progress.last_day.as_str(),
+            None => true,
+        }
+    }
+
+    pub fn is_new_post(&self, dir: &str, post: &SlackPost) -> bool {
+        match (self.conversations.get(dir), post.str_field("ts")) {
+            (Some(progress), Some(ts)) => ts_key(ts) > ts_key(&progress.last_ts),
+            _ => true,
+        }
+    }
+}
+
+fn day_of(filename: &str) -> &str {
+    filename
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(".json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_state_since() {
+        let posts: Vec<SlackPost> = serde_json::from_value(json!([
+            {"type": "message", "text": "a", "ts": "1600000001.000200"},
+            {"type": "message", "text": "b", "ts": "1600000009.000100"}
+        ]))
+        .unwrap();
+        let mut state = State::default();
+        state.add_day("general/2020-09-13.json", &posts[..1]);
+        state.add_day("general/2020-09-14.json", &posts);
+        assert_eq!(
+            state.conversations["general"],
+            Progress {
+                last_day: "2020-09-14".to_string(),
+                last_ts: "1600000009.000100".to_string(),
+            }
+        );
+
+        assert!(!state.is_new_day("general/2020-09-13.json"));
+        assert!(state.is_new_day("general/2020-09-14.json"));
+        assert!(state.is_new_day("random/2020-01-01.json"));
+
+        let newer: SlackPost =
+            serde_json::from_value(json!({"type": "message", "ts": "1600000010.000001"})).unwrap();
+        assert!(!state.is_new_post("general", &posts[1]));
+        assert!(state.is_new_post("general", &newer));
+
+        let next = State {
+            since: Some(state.conversations.clone()),
+            ..Default::default()
+        };
+        let next: State = serde_json::from_slice(&serde_json::to_vec(&next).unwrap()).unwrap();
+        let previous = next.previous().unwrap();
+        assert!(!previous.is_new_day("general/2020-09-13.json"));
+        assert!(State::default().previous().is_none());
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::model::url_extension;
+
+const ALIAS_PREFIX: &str = "alias:";
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Emoji {
+    pub name: String,
+    pub url: String,
+}
+
+impl Emoji {
+    // Image path inside the chunk `data/` directory
+    pub fn image_path(&self) -> String {
+        let ext = url_extension(&self.url).unwrap_or_else(|| String::from("png"));
+        format!("emoji/{}.{}", self.name, ext)
+    }
+
+    // Mattermost bulk import line
+    pub fn to_import_line(&self) -> Value {
+        json!({
+            "type": "emoji",
+            "emoji": {
+                "name": self.name,
+                "image": self.image_path(),
+            }
+        })
+    }
+}
+
+// Parse emoji listing. Supported formats:
+// - `emoji.list` API response: {"ok": true, "emoji": {"name": "url"}}
+// - plain map: {"name": "url"}
+// - list: [{"name": "name", "url": "url"}]
+//
+// Aliases (`alias:target`) are resolved to the target image,
+// aliases to standard emoji are skipped.
+pub fn parse_emoji_list(data: &[u8]) -> Result<Vec<Emoji>> {
+    let value: Value = serde_json::from_slice(data)?;
+    let listing: BTreeMap<String, String> = match value {
+        Value::Object(mut map) if map.contains_key("emoji") => {
+            serde_json::from_value(map.remove("emoji").unwrap_or_default())?
+        }
+        Value::Object(_) => serde_json::from_value(value)?,
+        Value::Array(_) => serde_json::from_value::<Vec<Emoji>>(value)?
+            .into_iter()
+            .map(|e| (e.name, e.url))
+            .collect(),
+        _ => bail!("Unsupported emoji listing format"),
+    };
+
+    let mut emoji = Vec::new();
+    for (name, url) in listing.iter() {
+        match url.strip_prefix(ALIAS_PREFIX) {
+            Some(target) => match listing.get(target) {
+                Some(target_url) if !target_url.starts_with(ALIAS_PREFIX) => emoji.push(Emoji {
+                    name: name.clone(),
+                    url: target_url.clone(),
+                }),
+                _ => warn!("Skip emoji alias {} to unknown emoji {}", name, target),
+            },
+            None => emoji.push(Emoji {
+                name: name.clone(),
+                url: url.clone(),
+            }),
+        }
+    }
+    Ok(emoji)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_emoji_list_resolves_aliases() {
+        let data = br#"{"ok": true, "emoji": {
+            "party": "https://emoji.slack-edge.com/T1/party/abc.gif",
+            "yay": "alias:party",
+            "thumbs": "alias:+1"
+        }}"#;
+
+        let emoji = parse_emoji_list(data).unwrap();
+
+        assert_eq!(emoji.len(), 2);
+        assert_eq!(emoji[0].name, "party");
+        assert_eq!(emoji[1].name, "yay");
+        assert_eq!(emoji[1].url, emoji[0].url);
+        assert_eq!(emoji[1].image_path(), "emoji/yay.gif");
+    }
+
+    #[test]
+    fn test_emoji_import_line() {
+        let emoji = Emoji {
+            name: "blob".to_string(),
+            url: "https://emoji.slack-edge.com/T1/blob/abc".to_string(),
+        };
+
+        assert_eq!(
+            emoji.to_import_line(),
+            json!({"type": "emoji", "emoji": {"name": "blob", "image": "emoji/blob.png"}})
+        );
+    }
+}
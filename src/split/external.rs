@@ -0,0 +1,153 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::{
+    model::{SlackPost, User},
+    users,
+};
+
+// Slack id of the account created for `--external-user`
+pub const EXTERNAL_USER_ID: &str = "UEXTERNAL";
+
+// Author of Slack Connect posts missing from users.json
+#[derive(Debug, Default, Clone)]
+pub struct ExternalUser {
+    pub slack_id: String,
+    pub name: String,
+    pub real_name: String,
+    pub team: String,
+    pub mattermost_name: String,
+    pub posts: usize,
+    pub channels: BTreeSet<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExternalUserRow<'a> {
+    slack_id: &'a str,
+    name: &'a str,
+    real_name: &'a str,
+    team: &'a str,
+    mattermost_name: &'a str,
+    posts: usize,
+    channels: String,
+}
+
+impl ExternalUser {
+    pub fn from_post(slack_id: &str, post: &SlackPost) -> Self {
+        let profile = post.extra.get("user_profile");
+        let field = |key: &str| {
+            profile
+                .and_then(|p| p.get(key))
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string()
+        };
+        let team = post
+            .str_field("user_team")
+            .map(|t| t.to_string())
+            .unwrap_or_else(|| field("team"));
+        ExternalUser {
+            slack_id: slack_id.to_string(),
+            name: field("name"),
+            real_name: field("real_name"),
+            team,
+            ..Default::default()
+        }
+    }
+
+    // Placeholder user record, so the importer knows the author
+    pub fn placeholder(&self, post: &SlackPost) -> User {
+        let profile = post
+            .extra
+            .get("user_profile")
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+        let str_of = |key: &str| profile.get(key).cloned().unwrap_or(Value::Null);
+        let name = match self.name.is_empty() {
+            true => self.slack_id.to_lowercase(),
+            false => self.name.clone(),
+        };
+        serde_json::from_value(json!({
+            "id": self.slack_id,
+            "team_id": self.team,
+            "name": name,
+            "real_name": self.real_name,
+            "deleted": false,
+            "is_stranger": true,
+            "profile": {
+                "real_name": str_of("real_name"),
+                "display_name": str_of("display_name"),
+                "first_name": str_of("first_name"),
+                "image_72": str_of("image_72"),
+                "team": self.team,
+            }
+        }))
+        .unwrap_or_default()
+    }
+}
+
+// Account shared by all external authors when `--external-user` is set, the
+// name is normalized to Mattermost username rules
+pub fn external_account(name: &str) -> User {
+    let name = users::normalize_username(name, EXTERNAL_USER_ID);
+    users::service_account(EXTERNAL_USER_ID, &name, "External user", false)
+}
+
+pub fn to_csv<'a>(users: impl Iterator<Item = &'a ExternalUser>) -> Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for user in users {
+        writer.serialize(ExternalUserRow {
+            slack_id: &user.slack_id,
+            name: &user.name,
+            real_name: &user.real_name,
+            team: &user.team,
+            mattermost_name: &user.mattermost_name,
+            posts: user.posts,
+            channels: user.channels.iter().cloned().collect::<Vec<_>>().join(" "),
+        })?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_placeholder_from_user_profile() {
+        let post: SlackPost = serde_json::from_value(json!({
+            "type": "message",
+            "user": "UEXT1",
+            "user_team": "TPARTNER",
+            "team": "TACME",
+            "text": "hi from partner",
+            "user_profile": {
+                "name": "bob",
+                "real_name": "Bob Partner",
+                "display_name": "bob",
+                "image_72": "https://avatars.slack-edge.com/bob_72.png",
+                "team": "TPARTNER"
+            }
+        }))
+        .unwrap();
+
+        let external = ExternalUser::from_post("UEXT1", &post);
+        assert_eq!(external.name, "bob");
+        assert_eq!(external.team, "TPARTNER");
+
+        let user = external.placeholder(&post);
+        assert_eq!(user.id, "UEXT1");
+        assert_eq!(user.name, "bob");
+        assert_eq!(user.extra["is_stranger"], json!(true));
+        assert_eq!(user.extra["real_name"], json!("Bob Partner"));
+    }
+
+    #[test]
+    fn test_external_account_name() {
+        assert_eq!(external_account("Partner Users").name, "partner_users");
+        assert_eq!(external_account("partners").name, "partners");
+    }
+}
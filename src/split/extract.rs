@@ -0,0 +1,121 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{bail, Result};
+
+use super::{
+    grid::split_workspace,
+    model::{Channel, Direct},
+};
+
+// Channel by name or id, with the listing it comes from. `workspace/name`
+// picks the Enterprise Grid workspace, names found in several workspaces
+// are ambiguous.
+pub fn find_channel<'a>(
+    listings: &'a BTreeMap<String, Vec<Channel>>,
+    key: &str,
+) -> Result<Option<(&'a str, &'a Channel)>> {
+    let key = key.trim_start_matches('#');
+    let (workspace, name) = match key.split_once('/') {
+        Some((workspace, name)) => (Some(workspace), name),
+        None => (None, key),
+    };
+    let found: Vec<(&str, &Channel)> = listings
+        .iter()
+        .filter(|(listing, _)| workspace.is_none_or(|w| split_workspace(listing).0 == w))
+        .flat_map(|(listing, channels)| {
+            channels
+                .iter()
+                .filter(|c| c.id == name || c.name == name)
+                .map(move |c| (listing.as_str(), c))
+        })
+        .collect();
+    if found.len() > 1 {
+        let workspaces: Vec<&str> = found.iter().map(|(l, _)| split_workspace(l).0).collect();
+        bail!(
+            "Channel {} is found in workspaces {}, use workspace/name",
+            key,
+            workspaces.join(", ")
+        );
+    }
+    Ok(found.into_iter().next())
+}
+
+// Direct conversation with exactly these members
+pub fn find_direct<'a>(directs: &'a [Direct], members: &BTreeSet<String>) -> Option<&'a Direct> {
+    directs
+        .iter()
+        .find(|dm| dm.members.iter().cloned().collect::<BTreeSet<_>>() == *members)
+}
+
+// Group DM with exactly these members
+pub fn find_group<'a>(
+    listings: &'a BTreeMap<String, Vec<Channel>>,
+    members: &BTreeSet<String>,
+) -> Option<(&'a str, &'a Channel)> {
+    listings
+        .iter()
+        .filter(|(listing, _)| listing.ends_with("mpims.json"))
+        .find_map(|(listing, channels)| {
+            channels
+                .iter()
+                .find(|c| c.members.iter().cloned().collect::<BTreeSet<_>>() == *members)
+                .map(|c| (listing.as_str(), c))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_find_conversations() {
+        let listings: BTreeMap<String, Vec<Channel>> = BTreeMap::from([
+            (
+                "channels.json".to_string(),
+                serde_json::from_value(json!([{"id": "C1", "name": "general"}])).unwrap(),
+            ),
+            (
+                "mpims.json".to_string(),
+                serde_json::from_value(json!([
+                    {"id": "G1", "name": "mpdm-a--b--c-1", "members": ["U1", "U2", "U3"]}
+                ]))
+                .unwrap(),
+            ),
+        ]);
+        let directs: Vec<Direct> =
+            serde_json::from_value(json!([{"id": "D1", "members": ["U2", "U1"]}])).unwrap();
+
+        let channel = |key| find_channel(&listings, key).unwrap();
+        assert_eq!(channel("#general").unwrap().1.id, "C1");
+        assert_eq!(channel("C1").unwrap().0, "channels.json");
+        assert!(channel("random").is_none());
+
+        let pair = BTreeSet::from(["U1".to_string(), "U2".to_string()]);
+        assert_eq!(find_direct(&directs, &pair).unwrap().id, "D1");
+        assert!(find_group(&listings, &pair).is_none());
+        let group = BTreeSet::from(["U1".to_string(), "U2".to_string(), "U3".to_string()]);
+        assert_eq!(find_group(&listings, &group).unwrap().1.id, "G1");
+    }
+
+    #[test]
+    fn test_find_channel_in_workspace() {
+        let listings: BTreeMap<String, Vec<Channel>> = ["acme", "beta"]
+            .iter()
+            .enumerate()
+            .map(|(idx, workspace)| {
+                (
+                    format!("teams/{}/channels.json", workspace),
+                    serde_json::from_value(json!([{"id": format!("C{}", idx), "name": "general"}]))
+                        .unwrap(),
+                )
+            })
+            .collect();
+
+        assert!(find_channel(&listings, "general").is_err());
+        let (listing, channel) = find_channel(&listings, "#beta/general").unwrap().unwrap();
+        assert_eq!(listing, "teams/beta/channels.json");
+        assert_eq!(channel.id, "C1");
+        assert!(find_channel(&listings, "gamma/general").unwrap().is_none());
+    }
+}
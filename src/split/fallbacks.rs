@@ -0,0 +1,208 @@
+use serde_json::{json, Value};
+
+use super::model::SlackPost;
+
+// File types Mattermost cannot import, shown as links instead
+static UNSUPPORTED_FILETYPES: &[&str] = &["quip", "canvas", "list"];
+
+// Replace huddles, calls and canvases with a readable summary,
+// original payload is kept in post props
+pub fn apply_fallbacks(post: &mut SlackPost) {
+    let mut summary: Vec<String> = Vec::new();
+
+    if matches!(
+        post.str_field("subtype"),
+        Some("huddle_thread") | Some("sh_room_created")
+    ) {
+        if let Some(room) = post.extra.get("room").cloned() {
+            summary.push(render_room(&room));
+            post.merge_props(json!({"slack_room": room}));
+        }
+    }
+
+    let calls: Vec<Value> = post
+        .extra
+        .get("blocks")
+        .and_then(|b| b.as_array())
+        .into_iter()
+        .flatten()
+        .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("call"))
+        .cloned()
+        .collect();
+    for call in calls.iter() {
+        if let Some(v1) = call.get("call").and_then(|c| c.get("v1")) {
+            summary.push(render_call(v1));
+        }
+    }
+    if !calls.is_empty() {
+        post.merge_props(json!({"slack_calls": calls}));
+    }
+
+    let (unsupported, files): (Vec<_>, Vec<_>) =
+        std::mem::take(&mut post.files).into_iter().partition(|f| {
+            ["filetype", "mode"].iter().any(|k| {
+                f.extra
+                    .get(*k)
+                    .and_then(|v| v.as_str())
+                    .map(|v| UNSUPPORTED_FILETYPES.contains(&v))
+                    .unwrap_or_default()
+            })
+        });
+    post.files = files;
+    for file in unsupported.iter() {
+        let title = file
+            .extra
+            .get("title")
+            .and_then(|t| t.as_str())
+            .into_iter()
+            .chain([file.name.as_str(), "Untitled"])
+            .find(|t| !t.is_empty())
+            .unwrap_or_default();
+        let kind = match file.extra.get("filetype").and_then(|t| t.as_str()) {
+            Some("list") => "List",
+            _ => "Canvas",
+        };
+        match file.extra.get("permalink").and_then(|p| p.as_str()) {
+            Some(link) => summary.push(format!(":page_facing_up: {}: [{}]({})", kind, title, link)),
+            None => summary.push(format!(":page_facing_up: {}: {}", kind, title)),
+        }
+    }
+    if !unsupported.is_empty() {
+        post.merge_props(json!({"slack_files": unsupported}));
+    }
+
+    if summary.is_empty() {
+        return;
+    }
+    let text = post.text().unwrap_or_default().trim().to_string();
+    let summary = summary.join("\n");
+    match text.is_empty() {
+        true => post.set("text", summary),
+        false => post.set("text", format!("{}\n\n{}", text, summary)),
+    }
+}
+
+fn render_room(room: &Value) -> String {
+    let name = room
+        .get("name")
+        .and_then(|n| n.as_str())
+        .unwrap_or_default();
+    let participants: Vec<String> = ["participant_history", "participants"]
+        .iter()
+        .filter_map(|k| room.get(*k).and_then(|p| p.as_array()))
+        .find(|p| !p.is_empty())
+        .into_iter()
+        .flatten()
+        .filter_map(|u| u.as_str())
+        .map(|u| format!("<@{}>", u))
+        .collect();
+
+    let mut line = String::from(":telephone_receiver: Huddle");
+    if !name.is_empty() {
+        line.push_str(&format!(" \"{}\"", name));
+    }
+    if let Some(duration) = duration(room.get("date_start"), room.get("date_end")) {
+        line.push_str(&format!(", {}", duration));
+    }
+    if !participants.is_empty() {
+        line.push_str(&format!(" with {}", participants.join(", ")));
+    }
+    line
+}
+
+fn render_call(call: &Value) -> String {
+    let name = call
+        .get("name")
+        .and_then(|n| n.as_str())
+        .unwrap_or_default();
+    let participants: Vec<String> = call
+        .get("all_participants")
+        .and_then(|p| p.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|p| p.get("slack_id").and_then(|u| u.as_str()))
+        .map(|u| format!("<@{}>", u))
+        .collect();
+
+    let mut line = String::from(":telephone_receiver: Call");
+    if !name.is_empty() {
+        line.push_str(&format!(" \"{}\"", name));
+    }
+    if let Some(duration) = duration(call.get("date_start"), call.get("date_end")) {
+        line.push_str(&format!(", {}", duration));
+    }
+    if !participants.is_empty() {
+        line.push_str(&format!(" with {}", participants.join(", ")));
+    }
+    if let Some(link) = call.get("join_url").and_then(|u| u.as_str()) {
+        line.push_str(&format!(" ({})", link));
+    }
+    line
+}
+
+fn duration(start: Option<&Value>, end: Option<&Value>) -> Option<String> {
+    let start = start.and_then(|s| s.as_i64())?;
+    let end = end.and_then(|e| e.as_i64()).filter(|e| *e > start)?;
+    let minutes = (end - start) / 60;
+    match minutes {
+        0 => Some(format!("{}s", end - start)),
+        m if m < 60 => Some(format!("{}m", m)),
+        m => Some(format!("{}h {}m", m / 60, m % 60)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_huddle_fallback() {
+        let mut post: SlackPost = serde_json::from_value(json!({
+            "type": "message",
+            "subtype": "huddle_thread",
+            "text": "",
+            "room": {
+                "id": "R1",
+                "name": "standup",
+                "date_start": 1600000000,
+                "date_end": 1600003900,
+                "participant_history": ["U1", "U2"]
+            }
+        }))
+        .unwrap();
+
+        apply_fallbacks(&mut post);
+
+        assert_eq!(
+            post.text(),
+            Some(":telephone_receiver: Huddle \"standup\", 1h 5m with <@U1>, <@U2>")
+        );
+        assert_eq!(post.extra["props"]["slack_room"]["id"], json!("R1"));
+    }
+
+    #[test]
+    fn test_canvas_fallback() {
+        let mut post: SlackPost = serde_json::from_value(json!({
+            "type": "message",
+            "text": "see plan",
+            "files": [
+                {"id": "F1", "name": "plan", "title": "Q3 plan", "filetype": "quip",
+                 "permalink": "https://acme.slack.com/docs/T1/F1"},
+                {"id": "F2", "name": "a.png", "url_private": "https://files.slack.com/a.png"}
+            ]
+        }))
+        .unwrap();
+
+        apply_fallbacks(&mut post);
+
+        assert_eq!(post.files.len(), 1);
+        assert_eq!(post.files[0].id, "F2");
+        assert_eq!(
+            post.text(),
+            Some(
+                "see plan\n\n:page_facing_up: Canvas: [Q3 plan](https://acme.slack.com/docs/T1/F1)"
+            )
+        );
+        assert_eq!(post.extra["props"]["slack_files"][0]["id"], json!("F1"));
+    }
+}
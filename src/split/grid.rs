@@ -0,0 +1,57 @@
+// Enterprise Grid exports keep every workspace under `teams/<workspace>/`,
+// org-wide files (org_users.json, dms.json, mpims.json) stay in the root.
+const TEAMS_DIR: &str = "teams/";
+
+// Split archive path to workspace and path relative to the workspace,
+// workspace is empty for regular exports and org-wide files
+pub fn split_workspace(filename: &str) -> (&str, &str) {
+    filename
+        .strip_prefix(TEAMS_DIR)
+        .and_then(|rest| rest.split_once('/'))
+        .unwrap_or(("", filename))
+}
+
+// Archive path of the file inside workspace
+pub fn workspace_path(workspace: &str, name: &str) -> String {
+    match workspace.is_empty() {
+        true => name.to_string(),
+        false => format!("{}{}/{}", TEAMS_DIR, workspace, name),
+    }
+}
+
+// Channel directory of the day file, including workspace prefix
+pub fn dir_of(filename: &str) -> &str {
+    filename
+        .rsplit_once('/')
+        .map(|(dir, _)| dir)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_workspace() {
+        assert_eq!(
+            split_workspace("general/2020-09-13.json"),
+            ("", "general/2020-09-13.json")
+        );
+        assert_eq!(split_workspace("org_users.json"), ("", "org_users.json"));
+        assert_eq!(
+            split_workspace("teams/acme/general/2020-09-13.json"),
+            ("acme", "general/2020-09-13.json")
+        );
+        assert_eq!(
+            split_workspace("teams/acme/channels.json"),
+            ("acme", "channels.json")
+        );
+        assert_eq!(workspace_path("acme", "general"), "teams/acme/general");
+        assert_eq!(workspace_path("", "general"), "general");
+        assert_eq!(
+            dir_of("teams/acme/general/2020-09-13.json"),
+            "teams/acme/general"
+        );
+        assert_eq!(dir_of("users.json"), "");
+    }
+}
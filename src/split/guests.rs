@@ -0,0 +1,197 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    str::FromStr,
+};
+
+use anyhow::{bail, Error, Result};
+use serde::{Deserialize, Serialize};
+
+use super::model::User;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuestPolicy {
+    // Restricted Slack accounts become Mattermost guests
+    #[default]
+    Guest,
+    // Import restricted accounts as regular members
+    Member,
+}
+
+impl FromStr for GuestPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "guest" => Ok(GuestPolicy::Guest),
+            "member" => Ok(GuestPolicy::Member),
+            _ => bail!("Unknown guest policy {}, expected guest or member", s),
+        }
+    }
+}
+
+// Restricted account and channels it is a member of
+#[derive(Debug, Default, Clone)]
+pub struct Guest {
+    pub slack_id: String,
+    pub username: String,
+    // Single-channel guest in Slack terms
+    pub single_channel: bool,
+    pub channels: BTreeSet<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct GuestRow<'a> {
+    slack_id: &'a str,
+    username: &'a str,
+    kind: &'a str,
+    channels: String,
+}
+
+pub fn is_guest(user: &User) -> bool {
+    user.is_restricted || user.is_ultra_restricted
+}
+
+// Guests by Slack id, collects channel memberships and checks them against
+// the allowed channels list (any channel is allowed when the list is empty).
+// `workspace/name` allows the channel of one Enterprise Grid workspace, a bare
+// name allows it in every workspace.
+#[derive(Debug, Default)]
+pub struct Guests {
+    pub guests: BTreeMap<String, Guest>,
+    allowed_channels: BTreeSet<String>,
+}
+
+impl Guests {
+    pub fn new(users: &[User], allowed_channels: &[String]) -> Self {
+        Guests {
+            guests: users
+                .iter()
+                .filter(|u| is_guest(u))
+                .map(|u| {
+                    let guest = Guest {
+                        slack_id: u.id.clone(),
+                        username: u.name.clone(),
+                        single_channel: u.is_ultra_restricted,
+                        ..Default::default()
+                    };
+                    (u.id.clone(), guest)
+                })
+                .collect(),
+            allowed_channels: allowed_channels
+                .iter()
+                .map(|c| c.trim_start_matches('#').to_string())
+                .collect(),
+        }
+    }
+
+    // Keep guests only in allowed channels, returns ids of removed guests.
+    // Workspace is empty for regular exports.
+    pub fn check_members(
+        &mut self,
+        workspace: &str,
+        channel: &str,
+        members: &mut Vec<String>,
+    ) -> Vec<String> {
+        let name = match workspace.is_empty() {
+            true => channel.to_string(),
+            false => format!("{}/{}", workspace, channel),
+        };
+        let allowed = self.allowed_channels.is_empty()
+            || self.allowed_channels.contains(channel)
+            || self.allowed_channels.contains(&name);
+        let mut removed = Vec::new();
+        members.retain(|id| {
+            let Some(guest) = self.guests.get_mut(id) else {
+                return true;
+            };
+            if !allowed {
+                removed.push(id.clone());
+                return false;
+            }
+            guest.channels.insert(name.clone());
+            true
+        });
+        removed
+    }
+
+    // Single-channel guests which ended up in several channels
+    pub fn overflowed(&self) -> impl Iterator<Item = &Guest> {
+        self.guests
+            .values()
+            .filter(|g| g.single_channel && g.channels.len() > 1)
+    }
+
+    pub fn to_csv(&self) -> Result<Vec<u8>> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for guest in self.guests.values() {
+            writer.serialize(GuestRow {
+                slack_id: &guest.slack_id,
+                username: &guest.username,
+                kind: match guest.single_channel {
+                    true => "single_channel",
+                    false => "multi_channel",
+                },
+                channels: guest.channels.iter().cloned().collect::<Vec<_>>().join(" "),
+            })?;
+        }
+        Ok(writer.into_inner()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_guest_memberships() {
+        let users: Vec<User> = serde_json::from_value(json!([
+            {"id": "U1", "name": "alice"},
+            {"id": "U2", "name": "bob", "is_restricted": true},
+            {"id": "U3", "name": "carol", "is_restricted": true, "is_ultra_restricted": true}
+        ]))
+        .unwrap();
+        let mut guests = Guests::new(&users, &["partners".to_string(), "support".to_string()]);
+        assert_eq!(guests.guests.len(), 2);
+
+        let mut members = vec!["U1".to_string(), "U2".to_string(), "U3".to_string()];
+        assert_eq!(
+            guests.check_members("", "general", &mut members),
+            vec!["U2", "U3"]
+        );
+        assert_eq!(members, vec!["U1"]);
+
+        let mut members = vec!["U1".to_string(), "U3".to_string()];
+        assert!(guests
+            .check_members("", "partners", &mut members)
+            .is_empty());
+        assert!(guests.check_members("", "support", &mut members).is_empty());
+        assert_eq!(members, vec!["U1", "U3"]);
+        assert_eq!(guests.overflowed().next().unwrap().slack_id, "U3");
+    }
+
+    #[test]
+    fn test_guest_channels_in_workspace() {
+        let users: Vec<User> =
+            serde_json::from_value(json!([{"id": "U2", "name": "bob", "is_restricted": true}]))
+                .unwrap();
+        let mut guests = Guests::new(&users, &["#acme/general".to_string(), "help".to_string()]);
+
+        let mut members = vec!["U2".to_string()];
+        assert!(guests
+            .check_members("acme", "general", &mut members)
+            .is_empty());
+        assert!(guests
+            .check_members("beta", "help", &mut members)
+            .is_empty());
+        assert_eq!(
+            guests.check_members("beta", "general", &mut members),
+            vec!["U2"]
+        );
+        assert_eq!(
+            guests.guests["U2"].channels,
+            BTreeSet::from(["acme/general".to_string(), "beta/help".to_string()])
+        );
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use anyhow::{bail, Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::model::{ts_to_millis, SlackPost};
+
+const TOMBSTONE_TEXT: &str = "_This message was deleted._";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeletedPolicy {
+    // Remove deleted messages, thread parents are kept as tombstones
+    #[default]
+    Drop,
+    // Replace deleted messages content with a placeholder
+    Tombstone,
+}
+
+impl FromStr for DeletedPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "drop" => Ok(DeletedPolicy::Drop),
+            "tombstone" => Ok(DeletedPolicy::Tombstone),
+            _ => bail!(
+                "Unknown deleted messages policy {}, expected drop or tombstone",
+                s
+            ),
+        }
+    }
+}
+
+// Edits and deletions of a conversation collected across its day files,
+// the event may be in a later day file than the message it changes
+#[derive(Debug, Default)]
+pub struct History {
+    // Latest version of edited messages by ts
+    edits: HashMap<String, SlackPost>,
+    deleted: HashSet<String>,
+    originals: HashSet<String>,
+}
+
+impl History {
+    // Day files must be added in chronological order, later edits win
+    pub fn add_day(&mut self, posts: &[SlackPost]) {
+        for post in posts {
+            match post.str_field("subtype") {
+                Some("message_deleted") => {
+                    if let Some(ts) = post.str_field("deleted_ts") {
+                        self.deleted.insert(ts.to_string());
+                    }
+                }
+                Some("message_changed") => {
+                    let Some(message) = post
+                        .extra
+                        .get("message")
+                        .and_then(|m| serde_json::from_value::<SlackPost>(m.clone()).ok())
+                    else {
+                        continue;
+                    };
+                    if let Some(ts) = message.str_field("ts") {
+                        self.edits.insert(ts.to_string(), message);
+                    }
+                }
+                subtype => {
+                    if let Some(ts) = post.str_field("ts") {
+                        if subtype == Some("tombstone") {
+                            self.deleted.insert(ts.to_string());
+                        }
+                        self.originals.insert(ts.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    // Edits of messages missing from every day file, these are dropped
+    pub fn unresolved(&self) -> usize {
+        self.edits
+            .keys()
+            .filter(|ts| !self.originals.contains(*ts))
+            .count()
+    }
+}
+
+// Collapse `message_changed`/`message_deleted` events of a day file into final
+// posts using the conversation history, apply deleted messages policy and
+// optionally keep edit metadata
+pub fn apply_history(
+    posts: Vec<SlackPost>,
+    history: &History,
+    preserve_edits: bool,
+    deleted_policy: DeletedPolicy,
+) -> Vec<SlackPost> {
+    posts
+        .into_iter()
+        .filter(|post| {
+            !matches!(
+                post.str_field("subtype"),
+                Some("message_deleted" | "message_changed")
+            )
+        })
+        .filter_map(|mut post| {
+            let ts = post.str_field("ts").map(String::from);
+            if let Some(edit) = ts.as_ref().and_then(|ts| history.edits.get(ts)) {
+                if post.str_field("subtype") != Some("tombstone") {
+                    post = edit.clone();
+                }
+            }
+            let is_deleted = ts
+                .map(|ts| history.deleted.contains(&ts))
+                .unwrap_or_default();
+            if is_deleted {
+                let has_replies = post
+                    .extra
+                    .get("reply_count")
+                    .and_then(|c| c.as_u64())
+                    .unwrap_or_default()
+                    > 0;
+                if deleted_policy == DeletedPolicy::Drop && !has_replies {
+                    return None;
+                }
+                tombstone(&mut post);
+            } else if preserve_edits {
+                preserve_edit(&mut post);
+            }
+            Some(post)
+        })
+        .collect()
+}
+
+fn tombstone(post: &mut SlackPost) {
+    post.set("text", TOMBSTONE_TEXT);
+    if let Some(extra) = post.extra.as_object_mut() {
+        extra.remove("attachments");
+        extra.remove("blocks");
+    }
+    post.file = None;
+    post.files.clear();
+    post.upload = false;
+    post.merge_props(json!({"deleted": true}));
+}
+
+fn preserve_edit(post: &mut SlackPost) {
+    let Some(edited) = post.extra.get("edited").cloned() else {
+        return;
+    };
+    if let Some(edit_at) = edited
+        .get("ts")
+        .and_then(|ts| ts.as_str())
+        .and_then(ts_to_millis)
+    {
+        post.set("edit_at", edit_at);
+    }
+    if let Some(user) = edited.get("user") {
+        post.merge_props(json!({"edited_by": user}));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn posts(value: serde_json::Value) -> Vec<SlackPost> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn history(days: &[&[SlackPost]]) -> History {
+        let mut history = History::default();
+        for day in days {
+            history.add_day(day);
+        }
+        history
+    }
+
+    #[test]
+    fn test_apply_history_collapses_events() {
+        let day = posts(json!([
+            {"type": "message", "user": "U1", "text": "helo", "ts": "1.000001"},
+            {"type": "message", "user": "U1", "text": "bye", "ts": "2.000001"},
+            {"type": "message", "subtype": "message_changed", "ts": "3.000001",
+             "message": {"type": "message", "user": "U1", "text": "hello", "ts": "1.000001",
+                         "edited": {"user": "U1", "ts": "3.000001"}}},
+            {"type": "message", "subtype": "message_deleted", "ts": "4.000001", "deleted_ts": "2.000001"}
+        ]));
+
+        let history = history(&[&day]);
+        let result = apply_history(day.clone(), &history, true, DeletedPolicy::Drop);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].text(), Some("hello"));
+        assert_eq!(result[0].extra["edit_at"], json!(3000));
+        assert_eq!(result[0].extra["props"]["edited_by"], json!("U1"));
+
+        let result = apply_history(day, &history, false, DeletedPolicy::Tombstone);
+        assert_eq!(result.len(), 2);
+        assert!(result[0].extra.get("edit_at").is_none());
+        assert_eq!(result[1].text(), Some(TOMBSTONE_TEXT));
+    }
+
+    #[test]
+    fn test_drop_keeps_thread_parents() {
+        let day = posts(json!([
+            {"type": "message", "subtype": "tombstone", "user": "USLACKBOT",
+             "text": "This message was deleted.", "ts": "1.000001", "reply_count": 2},
+            {"type": "message", "subtype": "tombstone", "user": "USLACKBOT",
+             "text": "This message was deleted.", "ts": "2.000001"}
+        ]));
+
+        let result = apply_history(day.clone(), &history(&[&day]), false, DeletedPolicy::Drop);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].text(), Some(TOMBSTONE_TEXT));
+    }
+
+    #[test]
+    fn test_apply_history_across_days() {
+        let first = posts(json!([
+            {"type": "message", "user": "U1", "text": "helo", "ts": "1.000001"},
+            {"type": "message", "user": "U1", "text": "bye", "ts": "2.000001"}
+        ]));
+        let second = posts(json!([
+            {"type": "message", "subtype": "message_changed", "ts": "90000.000001",
+             "message": {"type": "message", "user": "U1", "text": "hello", "ts": "1.000001"}},
+            {"type": "message", "subtype": "message_changed", "ts": "90001.000001",
+             "message": {"type": "message", "user": "U1", "text": "lost", "ts": "5.000001"}},
+            {"type": "message", "subtype": "message_deleted", "ts": "90002.000001",
+             "deleted_ts": "2.000001"}
+        ]));
+        let history = history(&[&first, &second]);
+        assert_eq!(history.unresolved(), 1);
+
+        let result = apply_history(first, &history, false, DeletedPolicy::Drop);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].text(), Some("hello"));
+        assert!(apply_history(second, &history, false, DeletedPolicy::Drop).is_empty());
+    }
+}
@@ -0,0 +1,237 @@
+use std::{fmt::Write, str::FromStr};
+
+use anyhow::{bail, Error, Result};
+use serde::{Deserialize, Serialize};
+
+use super::model::SlackPost;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            _ => bail!("Unknown output format {}, expected table or json", s),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelKind {
+    #[default]
+    Channel,
+    Group,
+    Mpim,
+    Direct,
+}
+
+impl ChannelKind {
+    // Kind by the shared file listing the channel
+    pub fn from_listing(filename: &str) -> Option<Self> {
+        match filename {
+            "channels.json" => Some(ChannelKind::Channel),
+            "groups.json" => Some(ChannelKind::Group),
+            "mpims.json" => Some(ChannelKind::Mpim),
+            "dms.json" => Some(ChannelKind::Direct),
+            _ => None,
+        }
+    }
+}
+
+// Statistics of a channel directory
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ChannelStats {
+    pub name: String,
+    pub kind: ChannelKind,
+    pub days: usize,
+    pub messages: usize,
+    pub first_day: String,
+    pub last_day: String,
+    pub attachments: usize,
+    pub attachment_bytes: u64,
+    // Size of day files in the archive, uncompressed
+    pub json_bytes: u64,
+}
+
+impl ChannelStats {
+    pub fn add_day(&mut self, filename: &str, size: u64, posts: &[SlackPost]) {
+        let day = filename
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches(".json")
+            .to_string();
+        if self.first_day.is_empty() || day < self.first_day {
+            self.first_day = day.clone();
+        }
+        if day > self.last_day {
+            self.last_day = day;
+        }
+        self.days += 1;
+        self.json_bytes += size;
+        self.messages += posts.len();
+        for file in posts
+            .iter()
+            .flat_map(|p| p.file.iter().chain(p.files.iter()))
+        {
+            self.attachments += 1;
+            self.attachment_bytes += file.size();
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Totals {
+    pub channels: usize,
+    pub groups: usize,
+    pub mpims: usize,
+    pub directs: usize,
+    pub users: usize,
+    pub messages: usize,
+    pub attachments: usize,
+    pub attachment_bytes: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Report {
+    pub archive: String,
+    pub totals: Totals,
+    pub channels: Vec<ChannelStats>,
+}
+
+impl Report {
+    pub fn new(archive: String, users: usize, channels: Vec<ChannelStats>) -> Self {
+        let mut totals = Totals {
+            users,
+            ..Default::default()
+        };
+        for stats in channels.iter() {
+            match stats.kind {
+                ChannelKind::Channel => totals.channels += 1,
+                ChannelKind::Group => totals.groups += 1,
+                ChannelKind::Mpim => totals.mpims += 1,
+                ChannelKind::Direct => totals.directs += 1,
+            }
+            totals.messages += stats.messages;
+            totals.attachments += stats.attachments;
+            totals.attachment_bytes += stats.attachment_bytes;
+        }
+        Report {
+            archive,
+            totals,
+            channels,
+        }
+    }
+
+    pub fn render(&self, format: OutputFormat) -> Result<String> {
+        match format {
+            OutputFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            OutputFormat::Table => Ok(self.to_table()),
+        }
+    }
+
+    fn to_table(&self) -> String {
+        let width = self
+            .channels
+            .iter()
+            .map(|c| c.name.len())
+            .max()
+            .unwrap_or_default()
+            .max("NAME".len());
+        let mut out = String::new();
+        let t = &self.totals;
+        let _ = writeln!(out, "Archive: {}", self.archive);
+        let _ = writeln!(
+            out,
+            "Users: {}, channels: {}, private: {}, group DMs: {}, DMs: {}",
+            t.users, t.channels, t.groups, t.mpims, t.directs
+        );
+        let _ = writeln!(
+            out,
+            "Messages: {}, attachments: {} ({})",
+            t.messages,
+            t.attachments,
+            human_bytes(t.attachment_bytes)
+        );
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "{:<width$}  {:<7}  {:>9}  {:<10}  {:<10}  {:>6}  {:>10}",
+            "NAME", "KIND", "MESSAGES", "FIRST", "LAST", "FILES", "SIZE"
+        );
+        for c in self.channels.iter() {
+            let kind = serde_json::to_value(c.kind).unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "{:<width$}  {:<7}  {:>9}  {:<10}  {:<10}  {:>6}  {:>10}",
+                c.name,
+                kind.as_str().unwrap_or_default(),
+                c.messages,
+                c.first_day,
+                c.last_day,
+                c.attachments,
+                human_bytes(c.attachment_bytes)
+            );
+        }
+        out
+    }
+}
+
+pub fn human_bytes(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.1} {}", value, units[unit]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_channel_stats() {
+        let posts: Vec<SlackPost> = serde_json::from_value(json!([
+            {"type": "message", "text": "hi", "ts": "1.0"},
+            {"type": "message", "text": "file", "ts": "2.0",
+             "files": [{"id": "F1", "name": "a.png", "size": 2048}]}
+        ]))
+        .unwrap();
+        let mut stats = ChannelStats {
+            name: "general".to_string(),
+            ..Default::default()
+        };
+        stats.add_day("general/2020-09-14.json", 100, &posts);
+        stats.add_day("general/2020-09-13.json", 50, &posts[..1]);
+
+        assert_eq!(stats.messages, 3);
+        assert_eq!(stats.first_day, "2020-09-13");
+        assert_eq!(stats.last_day, "2020-09-14");
+        assert_eq!(stats.attachment_bytes, 2048);
+
+        let report = Report::new("export.zip".to_string(), 2, vec![stats]);
+        assert_eq!(report.totals.channels, 1);
+        assert_eq!(human_bytes(report.totals.attachment_bytes), "2.0 KB");
+        assert!(report
+            .render(OutputFormat::Table)
+            .unwrap()
+            .contains("general  channel          3  2020-09-13  2020-09-14"));
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+};
+
+use anyhow::Result;
+use async_zip::{
+    base::{read::seek::ZipFileReader, write::ZipFileWriter},
+    Compression, ZipEntryBuilder, ZipString,
+};
+use futures::AsyncReadExt;
+use log::info;
+use serde_json::Value;
+use tokio::{fs::File, io::BufReader};
+use tokio_util::compat::Compat;
+
+use super::{
+    grid::{dir_of, split_workspace, workspace_path},
+    inspect::ChannelKind,
+    validate::{Category, Problems},
+};
+
+// Combine several Slack exports into one: listings are merged by id,
+// day files are concatenated and de-duplicated by `ts`.
+// Later exports win on conflicts.
+pub struct Merger {
+    inputs: Vec<PathBuf>,
+    readers: Vec<ZipFileReader<Compat<BufReader<File>>>>,
+    pub problems: Problems,
+}
+
+impl Merger {
+    pub async fn new(inputs: Vec<PathBuf>) -> Result<Self> {
+        let mut readers = Vec::with_capacity(inputs.len());
+        for input in inputs.iter() {
+            info!("Open {:?}", input);
+            readers
+                .push(ZipFileReader::with_tokio(BufReader::new(File::open(input).await?)).await?);
+        }
+        Ok(Merger {
+            inputs,
+            readers,
+            problems: Problems::default(),
+        })
+    }
+
+    pub async fn merge(&mut self, output: PathBuf) -> Result<()> {
+        // Archive path to (input, entry index) of every input
+        let mut entries: BTreeMap<String, Vec<(usize, usize)>> = BTreeMap::new();
+        for (input, reader) in self.readers.iter().enumerate() {
+            for (idx, entry) in reader.file().entries().iter().enumerate() {
+                if let Ok(name) = entry.filename().clone().into_string() {
+                    if !name.ends_with('/') {
+                        entries.entry(name).or_default().push((input, idx));
+                    }
+                }
+            }
+        }
+
+        let mut out_file = File::create(&output).await?;
+        let mut writer = ZipFileWriter::with_tokio(&mut out_file);
+
+        // Listings first, channel renames change day file directories
+        let mut renames: HashMap<(usize, String), String> = HashMap::new();
+        let listings: Vec<String> =
+            entries
+                .keys()
+                .filter(|name| ChannelKind::from_listing(split_workspace(name).1).is_some())
+                .chain(entries.keys().filter(|name| {
+                    matches!(split_workspace(name).1, "users.json" | "org_users.json")
+                }))
+                .cloned()
+                .collect();
+        for name in listings.iter() {
+            let mut lists = Vec::new();
+            for (input, idx) in entries[name].clone() {
+                lists.push((input, self.read_json(input, idx, name).await?));
+            }
+            let (merged, dirs) = merge_listing(name, lists, &mut self.problems);
+            renames.extend(dirs);
+            write_entry(&mut writer, name, &serde_json::to_vec(&merged)?).await?;
+        }
+
+        // Day files by output path
+        let mut days: BTreeMap<String, Vec<(usize, usize, String)>> = BTreeMap::new();
+        for (name, sources) in entries.iter() {
+            if listings.contains(name) {
+                continue;
+            }
+            let relative = split_workspace(name).1;
+            if !relative.contains('/') || !name.ends_with(".json") {
+                // Other shared files, the latest export wins
+                let (input, idx) = *sources.last().unwrap();
+                let data = self.read_entry(input, idx).await?;
+                write_entry(&mut writer, name, &data).await?;
+                continue;
+            }
+            let dir = dir_of(name);
+            let day = name.rsplit('/').next().unwrap_or_default();
+            for (input, idx) in sources {
+                let target = renames
+                    .get(&(*input, dir.to_string()))
+                    .map(|d| format!("{}/{}", d, day))
+                    .unwrap_or_else(|| name.clone());
+                days.entry(target)
+                    .or_default()
+                    .push((*input, *idx, name.clone()));
+            }
+        }
+
+        info!("Merge {} day files", days.len());
+        for (name, sources) in days {
+            let mut posts: Vec<Vec<Value>> = Vec::new();
+            for (input, idx, source) in sources {
+                match self.read_json(input, idx, &source).await? {
+                    Value::Array(day) => posts.push(day),
+                    _ => self.problems.warning(
+                        Category::Layout,
+                        &source,
+                        format!("not a list of posts in {:?}", self.inputs[input]),
+                    ),
+                }
+            }
+            let merged = merge_posts(posts);
+            write_entry(&mut writer, &name, &serde_json::to_vec(&merged)?).await?;
+        }
+
+        writer.close().await?;
+        info!("Merged export: {:?}", output);
+        Ok(())
+    }
+
+    async fn read_entry(&mut self, input: usize, idx: usize) -> Result<Vec<u8>> {
+        let mut reader = self.readers[input].reader_with_entry(idx).await?;
+        let mut buffer: Vec<u8> = Vec::new();
+        reader.read_to_end(&mut buffer).await?;
+        Ok(buffer)
+    }
+
+    // Unparsable files are reported and treated as empty lists
+    async fn read_json(&mut self, input: usize, idx: usize, name: &str) -> Result<Value> {
+        let buffer = self.read_entry(input, idx).await?;
+        match serde_json::from_slice(&buffer) {
+            Ok(value) => Ok(value),
+            Err(e) => {
+                self.problems.error(
+                    Category::Json,
+                    name,
+                    format!("{} in {:?}", e, self.inputs[input]),
+                );
+                Ok(Value::Array(Vec::new()))
+            }
+        }
+    }
+}
+
+// Merge entries of a listing by id. Returns the listing and directory
+// renames of every input for channels known under other names.
+pub fn merge_listing(
+    name: &str,
+    lists: Vec<(usize, Value)>,
+    problems: &mut Problems,
+) -> (Vec<Value>, HashMap<(usize, String), String>) {
+    let workspace = split_workspace(name).0;
+    let mut merged: Vec<Value> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    // Names of every input by id
+    let mut names: Vec<(usize, String, String)> = Vec::new();
+    for (input, list) in lists {
+        let Value::Array(items) = list else {
+            problems.error(
+                Category::Json,
+                name,
+                format!("not a list in input {}", input + 1),
+            );
+            continue;
+        };
+        for item in items {
+            let id = str_of(&item, "id");
+            let item_name = str_of(&item, "name");
+            if id.is_empty() {
+                continue;
+            }
+            names.push((input, id.clone(), item_name.clone()));
+            match positions.get(&id) {
+                Some(pos) => {
+                    let previous = str_of(&merged[*pos], "name");
+                    if previous != item_name {
+                        problems.warning(
+                            Category::Rename,
+                            name,
+                            format!("{} is renamed from {} to {}", id, previous, item_name),
+                        );
+                    }
+                    merged[*pos] = item;
+                }
+                None => {
+                    positions.insert(id, merged.len());
+                    merged.push(item);
+                }
+            }
+        }
+    }
+
+    // Channels with the same name and different ids get unique names
+    let is_channels = !matches!(
+        split_workspace(name).1,
+        "users.json" | "org_users.json" | "dms.json"
+    );
+    let mut taken: HashMap<String, String> = HashMap::new();
+    for item in merged.iter_mut().filter(|_| is_channels) {
+        let id = str_of(item, "id");
+        let mut item_name = str_of(item, "name");
+        if taken.get(&item_name).is_some_and(|other| *other != id) {
+            let unique = format!("{}-{}", item_name, id.to_lowercase());
+            problems.warning(
+                Category::Duplicate,
+                name,
+                format!(
+                    "channel name {} is used by {} and {}, renamed to {}",
+                    item_name, taken[&item_name], id, unique
+                ),
+            );
+            item["name"] = Value::String(unique.clone());
+            item_name = unique;
+        }
+        taken.insert(item_name, id);
+    }
+
+    let mut renames = HashMap::new();
+    if is_channels {
+        let finals: HashMap<String, String> = merged
+            .iter()
+            .map(|item| (str_of(item, "id"), str_of(item, "name")))
+            .collect();
+        for (input, id, item_name) in names {
+            if let Some(last) = finals.get(&id).filter(|last| **last != item_name) {
+                renames.insert(
+                    (input, workspace_path(workspace, &item_name)),
+                    workspace_path(workspace, last),
+                );
+            }
+        }
+    }
+    (merged, renames)
+}
+
+fn str_of(value: &Value, key: &str) -> String {
+    value
+        .get(key)
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+// Concatenate posts of the same day, later copies of a `ts` win
+pub fn merge_posts(days: Vec<Vec<Value>>) -> Vec<Value> {
+    let mut merged: Vec<Value> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for post in days.into_iter().flatten() {
+        let ts = str_of(&post, "ts");
+        match positions.get(&ts).filter(|_| !ts.is_empty()) {
+            Some(pos) => merged[*pos] = post,
+            None => {
+                positions.insert(ts, merged.len());
+                merged.push(post);
+            }
+        }
+    }
+    merged.sort_by_key(|post| ts_key(&str_of(post, "ts")));
+    merged
+}
+
+pub fn ts_key(ts: &str) -> (u64, u64) {
+    let (secs, micros) = ts.split_once('.').unwrap_or((ts, "0"));
+    (
+        secs.parse().unwrap_or_default(),
+        micros.parse().unwrap_or_default(),
+    )
+}
+
+async fn write_entry(
+    writer: &mut ZipFileWriter<Compat<&mut File>>,
+    name: &str,
+    data: &[u8],
+) -> Result<()> {
+    let builder = ZipEntryBuilder::new(ZipString::from(name), Compression::Deflate);
+    writer.write_entry_whole(builder, data).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_posts() {
+        let full = vec![
+            json!({"ts": "1600000002.000100", "text": "second"}),
+            json!({"ts": "1600000001.000100", "text": "first"}),
+        ];
+        let incremental = vec![
+            json!({"ts": "1600000002.000100", "text": "second, edited"}),
+            json!({"ts": "1600000010.000100", "text": "third"}),
+        ];
+
+        let merged = merge_posts(vec![full, incremental]);
+
+        let texts: Vec<&str> = merged.iter().map(|p| p["text"].as_str().unwrap()).collect();
+        assert_eq!(texts, vec!["first", "second, edited", "third"]);
+    }
+
+    #[test]
+    fn test_merge_listing_renames() {
+        let old = json!([{"id": "C1", "name": "dev"}, {"id": "C2", "name": "ops"}]);
+        let new = json!([{"id": "C1", "name": "engineering"}, {"id": "C3", "name": "ops"}]);
+        let mut problems = Problems::default();
+
+        let (merged, renames) =
+            merge_listing("channels.json", vec![(0, old), (1, new)], &mut problems);
+
+        let names: Vec<&str> = merged.iter().map(|c| c["name"].as_str().unwrap()).collect();
+        assert_eq!(names, vec!["engineering", "ops", "ops-c3"]);
+        assert_eq!(renames[&(0, "dev".to_string())], "engineering");
+        assert_eq!(renames[&(1, "ops".to_string())], "ops-c3");
+        let categories: Vec<Category> = problems.problems.iter().map(|p| p.category).collect();
+        assert_eq!(categories, vec![Category::Rename, Category::Duplicate]);
+    }
+}
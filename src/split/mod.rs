@@ -1,6 +1,25 @@
+pub mod bots;
 pub mod config;
+pub mod delta;
+pub mod emoji;
+pub mod external;
+pub mod extract;
+pub mod fallbacks;
+pub mod grid;
+pub mod guests;
+pub mod history;
+pub mod inspect;
+pub mod merge;
 pub mod model;
+pub mod plan;
+pub mod render;
+pub mod scripts;
 pub mod splitter;
+pub mod subtypes;
+pub mod teams;
+pub mod transcript;
+pub mod users;
+pub mod validate;
 
 pub use config::Config;
 pub use splitter::Splitter;
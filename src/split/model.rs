@@ -1,7 +1,18 @@
 use serde::{Deserialize, Serialize};
+use serde_aux::field_attributes::deserialize_number_from_string;
+
+// Mattermost channel header and purpose limits, in characters
+pub const MAX_HEADER_LEN: usize = 1024;
+pub const MAX_PURPOSE_LEN: usize = 250;
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct Chunk {
+    // Enterprise Grid workspace, empty for regular exports
+    #[serde(default)]
+    pub workspace: String,
+    // Mattermost team the chunk is imported into
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<String>,
     pub items: Vec<ChunkItem>,
 }
 
@@ -13,8 +24,125 @@ pub struct ChunkItem {
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct Direct {
+    #[serde(flatten)]
+    pub extra: serde_json::Value,
+
     pub id: String,
     pub members: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pins: Vec<Pin>,
+}
+
+// Pinned message reference, `id` is the message ts
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct Pin {
+    #[serde(flatten)]
+    pub extra: serde_json::Value,
+
+    #[serde(default)]
+    pub id: String,
+}
+
+// Entry of channels.json, groups.json and mpims.json
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct Channel {
+    #[serde(flatten)]
+    pub extra: serde_json::Value,
+
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, deserialize_with = "deserialize_number_from_string")]
+    pub created: i64,
+    #[serde(default)]
+    pub creator: String,
+    #[serde(default)]
+    pub is_archived: bool,
+    #[serde(default)]
+    pub members: Vec<String>,
+    // Mattermost channel header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic: Option<ChannelText>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub purpose: Option<ChannelText>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pins: Vec<Pin>,
+}
+
+impl Channel {
+    // Cut topic and purpose to Mattermost limits, returns true when truncated
+    pub fn truncate_texts(&mut self) -> bool {
+        let mut truncated = false;
+        for (text, limit) in [
+            (&mut self.topic, MAX_HEADER_LEN),
+            (&mut self.purpose, MAX_PURPOSE_LEN),
+        ] {
+            if let Some(text) = text.as_mut() {
+                if text.value.chars().count() > limit {
+                    text.value = text.value.chars().take(limit).collect();
+                    truncated = true;
+                }
+            }
+        }
+        truncated
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChannelText {
+    #[serde(default)]
+    pub value: String,
+    #[serde(default)]
+    pub creator: String,
+    #[serde(default, deserialize_with = "deserialize_number_from_string")]
+    pub last_set: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct User {
+    #[serde(flatten)]
+    pub extra: serde_json::Value,
+
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub deleted: bool,
+    // Multi-channel and single-channel guests
+    #[serde(default)]
+    pub is_restricted: bool,
+    #[serde(default)]
+    pub is_ultra_restricted: bool,
+    #[serde(default)]
+    pub profile: Profile,
+
+    // Path to the downloaded avatar inside the chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile_image: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct Profile {
+    #[serde(flatten)]
+    pub extra: serde_json::Value,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_original: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_512: Option<String>,
+}
+
+impl Profile {
+    pub fn avatar_url(&self) -> Option<String> {
+        vec![self.image_original.clone(), self.image_512.clone()]
+            .into_iter()
+            .flatten()
+            .find(|u| !u.is_empty())
+    }
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -31,6 +159,172 @@ pub struct SlackPost {
     pub files: Vec<File>,
 }
 
+impl SlackPost {
+    pub fn str_field(&self, key: &str) -> Option<&str> {
+        self.extra.get(key).and_then(|v| v.as_str())
+    }
+
+    pub fn user(&self) -> Option<&str> {
+        self.str_field("user")
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        self.str_field("text")
+    }
+
+    pub fn set(&mut self, key: &str, value: impl Into<serde_json::Value>) {
+        if let Some(extra) = self.extra.as_object_mut() {
+            extra.insert(key.to_string(), value.into());
+        }
+    }
+
+    // Merge values into Mattermost post `props`
+    pub fn merge_props(&mut self, props: serde_json::Value) {
+        let mut merged = self
+            .extra
+            .get("props")
+            .cloned()
+            .unwrap_or(serde_json::json!({}));
+        if let (Some(merged), Some(props)) = (merged.as_object_mut(), props.as_object()) {
+            for (k, v) in props {
+                merged.insert(k.clone(), v.clone());
+            }
+        }
+        self.set("props", merged);
+    }
+
+    pub fn is_pinned_to(&self) -> bool {
+        self.extra
+            .get("pinned_to")
+            .and_then(|p| p.as_array())
+            .map(|p| !p.is_empty())
+            .unwrap_or_default()
+    }
+
+    // Block Kit payload, `None` when missing or not parseable
+    pub fn blocks(&self) -> Option<Vec<Block>> {
+        self.extra
+            .get("blocks")
+            .and_then(|b| serde_json::from_value(b.clone()).ok())
+    }
+}
+
+// Block Kit layout blocks
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    RichText {
+        #[serde(default)]
+        elements: Vec<RichTextElement>,
+    },
+    Section {
+        text: Option<TextObject>,
+        #[serde(default)]
+        fields: Vec<TextObject>,
+    },
+    Header {
+        text: TextObject,
+    },
+    Context {
+        #[serde(default)]
+        elements: Vec<TextObject>,
+    },
+    Divider,
+    Image {
+        #[serde(default)]
+        image_url: String,
+        #[serde(default)]
+        alt_text: String,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TextObject {
+    // plain_text or mrkdwn
+    #[serde(default, rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RichTextElement {
+    RichTextSection {
+        #[serde(default)]
+        elements: Vec<RichTextInline>,
+    },
+    RichTextList {
+        #[serde(default)]
+        style: String,
+        #[serde(default)]
+        indent: usize,
+        #[serde(default)]
+        offset: usize,
+        #[serde(default)]
+        elements: Vec<RichTextElement>,
+    },
+    RichTextPreformatted {
+        #[serde(default)]
+        elements: Vec<RichTextInline>,
+    },
+    RichTextQuote {
+        #[serde(default)]
+        elements: Vec<RichTextInline>,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RichTextInline {
+    Text {
+        #[serde(default)]
+        text: String,
+        #[serde(default)]
+        style: TextStyle,
+    },
+    Link {
+        #[serde(default)]
+        url: String,
+        text: Option<String>,
+        #[serde(default)]
+        style: TextStyle,
+    },
+    User {
+        user_id: String,
+    },
+    Channel {
+        channel_id: String,
+    },
+    Usergroup {
+        usergroup_id: String,
+    },
+    Emoji {
+        name: String,
+    },
+    Broadcast {
+        range: String,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TextStyle {
+    #[serde(default)]
+    pub bold: bool,
+    #[serde(default)]
+    pub italic: bool,
+    #[serde(default)]
+    pub strike: bool,
+    #[serde(default)]
+    pub code: bool,
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct File {
     #[serde(flatten)]
@@ -50,6 +344,14 @@ pub struct File {
 }
 
 impl File {
+    // Size in bytes, as reported by Slack
+    pub fn size(&self) -> u64 {
+        self.extra
+            .get("size")
+            .and_then(|s| s.as_u64())
+            .unwrap_or_default()
+    }
+
     pub fn url_for_download(&self) -> String {
         vec![self.url_private_download.clone(), self.url_private.clone()]
             .into_iter()
@@ -58,6 +360,25 @@ impl File {
     }
 }
 
+// Slack message ts ("1600000000.000100") to unix milliseconds
+pub fn ts_to_millis(ts: &str) -> Option<i64> {
+    let (secs, fraction) = ts.split_once('.').unwrap_or((ts, "0"));
+    let millis: String = fraction.chars().chain("000".chars()).take(3).collect();
+    Some(secs.parse::<i64>().ok()? * 1000 + millis.parse::<i64>().ok()?)
+}
+
+// Lowercase file extension from the last url path segment
+pub fn url_extension(url: &str) -> Option<String> {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| {
+            u.path_segments()
+                .and_then(|mut s| s.next_back().map(|s| s.to_string()))
+        })
+        .and_then(|name| name.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()))
+        .filter(|ext| !ext.is_empty() && ext.len() <= 4)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -66,7 +387,9 @@ mod tests {
     #[test]
     fn test_slack_post_serialization() {
         let file = File {
-            extra: json!({"id": "F12345", "name": "example.txt"}),
+            extra: json!({"mimetype": "text/plain"}),
+            id: "F12345".to_string(),
+            name: "example.txt".to_string(),
             ..Default::default()
         };
         let slack_post = SlackPost {
@@ -91,7 +414,9 @@ mod tests {
     #[test]
     fn test_file_serialization() {
         let file = File {
-            extra: json!({"id": "F12345", "name": "example.txt", "size": 1024}),
+            extra: json!({"mimetype": "text/plain", "size": 1024}),
+            id: "F12345".to_string(),
+            name: "example.txt".to_string(),
             ..Default::default()
         };
 
@@ -99,5 +424,109 @@ mod tests {
         let deserialized: File = serde_json::from_str(&serialized).unwrap();
 
         assert_eq!(deserialized.extra, file.extra);
+        assert_eq!(deserialized.id, file.id);
+        assert_eq!(deserialized.name, file.name);
+    }
+
+    #[test]
+    fn test_direct_keeps_pins() {
+        let direct: Direct = serde_json::from_value(json!({
+            "id": "D12345",
+            "created": 1600000000,
+            "members": ["U1", "U2"],
+            "pins": [{"id": "1600000001.000100", "type": "C", "user": "U1"}]
+        }))
+        .unwrap();
+
+        assert_eq!(direct.pins[0].id, "1600000001.000100");
+
+        let serialized = serde_json::to_value(&direct).unwrap();
+        assert_eq!(serialized["created"], json!(1600000000));
+        assert_eq!(serialized["pins"][0]["user"], json!("U1"));
+    }
+
+    #[test]
+    fn test_channel_serialization() {
+        let mut channel: Channel = serde_json::from_value(json!({
+            "id": "C12345",
+            "name": "old",
+            "created": "1500000000",
+            "creator": "U1",
+            "is_archived": true,
+            "is_general": false,
+            "members": ["U1"],
+            "topic": {"value": "x".repeat(MAX_HEADER_LEN + 10), "creator": "U1", "last_set": 0},
+            "purpose": {"value": "Old stuff", "creator": "U1", "last_set": "1500000001"}
+        }))
+        .unwrap();
+
+        assert!(channel.is_archived);
+        assert_eq!(channel.created, 1500000000);
+        assert_eq!(channel.purpose.as_ref().unwrap().last_set, 1500000001);
+        assert!(channel.truncate_texts());
+        assert_eq!(channel.topic.as_ref().unwrap().value.len(), MAX_HEADER_LEN);
+
+        let serialized = serde_json::to_value(&channel).unwrap();
+        assert_eq!(serialized["is_archived"], json!(true));
+        assert_eq!(serialized["is_general"], json!(false));
+        assert_eq!(serialized["created"], json!(1500000000));
+    }
+
+    #[test]
+    fn test_blocks_deserialization() {
+        let post: SlackPost = serde_json::from_value(json!({
+            "text": "fallback",
+            "blocks": [
+                {"type": "rich_text", "block_id": "b1", "elements": [
+                    {"type": "rich_text_section", "elements": [
+                        {"type": "text", "text": "hi ", "style": {"bold": true}},
+                        {"type": "user", "user_id": "U12345"},
+                        {"type": "date", "timestamp": 1600000000}
+                    ]}
+                ]},
+                {"type": "actions", "elements": []}
+            ]
+        }))
+        .unwrap();
+
+        let blocks = post.blocks().unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[1], Block::Unknown);
+        let Block::RichText { elements } = &blocks[0] else {
+            panic!("rich_text block expected");
+        };
+        let RichTextElement::RichTextSection { elements } = &elements[0] else {
+            panic!("rich_text_section expected");
+        };
+        assert_eq!(
+            elements[1],
+            RichTextInline::User {
+                user_id: "U12345".to_string()
+            }
+        );
+        assert_eq!(elements[2], RichTextInline::Unknown);
+    }
+
+    #[test]
+    fn test_user_avatar_url() {
+        let user: User = serde_json::from_value(json!({
+            "id": "U12345",
+            "name": "alice",
+            "profile": {"image_512": "https://avatars.slack-edge.com/a_512.jpg", "title": ""}
+        }))
+        .unwrap();
+
+        assert_eq!(
+            user.profile.avatar_url().as_deref(),
+            Some("https://avatars.slack-edge.com/a_512.jpg")
+        );
+        assert_eq!(
+            url_extension(&user.profile.avatar_url().unwrap()).as_deref(),
+            Some("jpg")
+        );
+
+        let serialized = serde_json::to_value(&user).unwrap();
+        assert_eq!(serialized["profile"]["title"], json!(""));
+        assert!(serialized.get("profile_image").is_none());
     }
 }
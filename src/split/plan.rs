@@ -0,0 +1,203 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    inspect::ChannelStats,
+    model::{Chunk, ChunkItem},
+};
+
+// Chunk layout of a transform run, can be fed back with `transform --plan`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Plan {
+    pub archive: String,
+    pub num_chunks: usize,
+    pub directs: Vec<PlannedChunk>,
+    pub channels: Vec<PlannedChunk>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PlannedChunk {
+    // Output archive name
+    pub name: String,
+    #[serde(default)]
+    pub workspace: String,
+    // Mattermost team, taken from `--team` when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<String>,
+    pub messages: usize,
+    // Day files and attachments, uncompressed
+    pub estimated_bytes: u64,
+    pub channels: Vec<ChannelStats>,
+}
+
+impl Plan {
+    pub fn read(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)?;
+        serde_json::from_slice(&data).map_err(|e| anyhow!("Failed to read plan {:?}: {}", path, e))
+    }
+}
+
+impl PlannedChunk {
+    pub fn new(
+        name: String,
+        workspace: String,
+        channels: Vec<ChannelStats>,
+        with_files: bool,
+    ) -> Self {
+        let messages = channels.iter().map(|c| c.messages).sum();
+        let estimated_bytes = channels
+            .iter()
+            .map(|c| match with_files {
+                true => c.json_bytes + c.attachment_bytes,
+                false => c.json_bytes,
+            })
+            .sum();
+        PlannedChunk {
+            name,
+            workspace,
+            team: None,
+            messages,
+            estimated_bytes,
+            channels,
+        }
+    }
+
+    // Build chunk from directories listed in the plan, `files_idx` entries
+    // are removed, so leftovers are the directories missing from the plan
+    pub fn to_chunk(
+        &self,
+        files_idx: &mut HashMap<String, Vec<(String, usize)>>,
+    ) -> (Chunk, Vec<String>) {
+        let mut missing = Vec::new();
+        let mut chunk = Chunk {
+            workspace: self.workspace.clone(),
+            team: self.team.clone(),
+            ..Default::default()
+        };
+        for channel in self.channels.iter() {
+            match files_idx.remove(&channel.name) {
+                Some(files) => chunk.items.push(ChunkItem {
+                    id: channel.name.clone(),
+                    files,
+                }),
+                None => missing.push(channel.name.clone()),
+            }
+        }
+        (chunk, missing)
+    }
+}
+
+// Output archive names of chunks, numbered per workspace
+pub fn chunk_names(chunks: &[Chunk], prefix: &str, archive: &str) -> Vec<String> {
+    let mut workspace_idx: HashMap<&str, usize> = HashMap::new();
+    chunks
+        .iter()
+        .map(|chunk| {
+            let idx = workspace_idx.entry(chunk.workspace.as_str()).or_default();
+            let name = match chunk.workspace.is_empty() {
+                true => format!("{}_{:03}_{}", prefix, idx, archive),
+                false => format!("{}_{}_{:03}_{}", prefix, chunk.workspace, idx, archive),
+            };
+            *idx += 1;
+            name
+        })
+        .collect()
+}
+
+// Prefix and workspace of chunk archive name made by `chunk_names`
+pub fn parse_chunk_name<'a>(name: &'a str, archive: &str) -> Option<(&'a str, &'a str)> {
+    let stem = name.strip_suffix(archive)?.strip_suffix('_')?;
+    let (head, idx) = stem.rsplit_once('_')?;
+    if idx.len() != 3 || !idx.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    ["directs", "channels"].iter().find_map(|prefix| {
+        let rest = head.strip_prefix(prefix)?;
+        match rest.strip_prefix('_') {
+            Some(workspace) => Some((*prefix, workspace)),
+            None if rest.is_empty() => Some((*prefix, "")),
+            None => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(name: &str, messages: usize) -> ChannelStats {
+        ChannelStats {
+            name: name.to_string(),
+            messages,
+            json_bytes: 100,
+            attachment_bytes: 1000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_planned_chunk_roundtrip() {
+        let planned = PlannedChunk::new(
+            "channels_000_export.zip".to_string(),
+            String::new(),
+            vec![stats("general", 3), stats("random", 2)],
+            false,
+        );
+        assert_eq!(planned.messages, 5);
+        assert_eq!(planned.estimated_bytes, 200);
+
+        let mut planned: PlannedChunk =
+            serde_json::from_str(&serde_json::to_string(&planned).unwrap()).unwrap();
+        assert_eq!(planned.name, "channels_000_export.zip");
+        assert_eq!(planned.messages, 5);
+        assert_eq!(planned.channels.len(), 2);
+        assert_eq!(planned.channels[1].name, "random");
+        planned.team = Some("acme".to_string());
+
+        let mut files_idx = HashMap::from([
+            (
+                "random".to_string(),
+                vec![("random/2020-09-13.json".to_string(), 4)],
+            ),
+            (
+                "dev".to_string(),
+                vec![("dev/2020-09-13.json".to_string(), 5)],
+            ),
+        ]);
+        let (chunk, missing) = planned.to_chunk(&mut files_idx);
+        assert_eq!(chunk.items.len(), 1);
+        assert_eq!(chunk.items[0].id, "random");
+        assert_eq!(chunk.team.as_deref(), Some("acme"));
+        assert_eq!(missing, vec!["general"]);
+        assert!(files_idx.contains_key("dev"));
+
+        let chunks = vec![
+            Chunk::default(),
+            Chunk {
+                workspace: "acme".to_string(),
+                ..Default::default()
+            },
+            Chunk::default(),
+        ];
+        let names = chunk_names(&chunks, "channels", "export.zip");
+        assert_eq!(
+            names,
+            vec![
+                "channels_000_export.zip",
+                "channels_acme_000_export.zip",
+                "channels_001_export.zip"
+            ]
+        );
+        assert_eq!(
+            parse_chunk_name(&names[0], "export.zip"),
+            Some(("channels", ""))
+        );
+        assert_eq!(
+            parse_chunk_name(&names[1], "export.zip"),
+            Some(("channels", "acme"))
+        );
+        assert_eq!(parse_chunk_name("emoji_export.zip", "export.zip"), None);
+    }
+}
@@ -0,0 +1,431 @@
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use serde_json::{json, Map, Value};
+
+use super::model::{Block, RichTextElement, RichTextInline, SlackPost, TextObject, TextStyle};
+
+lazy_static! {
+    // Slack mrkdwn emphasis, links and code spans are matched to be kept as is
+    static ref MRKDWN_RE: Regex = Regex::new(
+        r"(?m)(?P<skip><[^>\n]*>|```[\s\S]*?```|`[^`\n]*`)|(?P<pre>^|[^\w*~])(?:\*(?P<bold>[^*\n]+)\*|_(?P<italic>[^_\n]+)_|~(?P<strike>[^~\n]+)~)"
+    )
+    .unwrap();
+}
+
+// Fields shared by Slack legacy attachments and Mattermost message attachments
+static ATTACHMENT_FIELDS: &[&str] = &[
+    "fallback",
+    "color",
+    "pretext",
+    "author_name",
+    "author_link",
+    "author_icon",
+    "title",
+    "title_link",
+    "text",
+    "fields",
+    "image_url",
+    "thumb_url",
+    "footer",
+    "footer_icon",
+    "ts",
+];
+
+fn str_of<'a>(value: &'a Value, key: &str) -> &'a str {
+    value.get(key).and_then(|v| v.as_str()).unwrap_or_default()
+}
+
+// Convert Slack legacy attachments to Mattermost `props.attachments`
+pub fn to_mattermost_attachments(attachments: &[Value]) -> Value {
+    Value::Array(
+        attachments
+            .iter()
+            .map(|a| {
+                let fields: Map<String, Value> = ATTACHMENT_FIELDS
+                    .iter()
+                    .filter_map(|f| a.get(*f).map(|v| (f.to_string(), v.clone())))
+                    .collect();
+                Value::Object(fields)
+            })
+            .collect(),
+    )
+}
+
+// Render Slack legacy attachments as Markdown quote blocks
+pub fn render_attachments(attachments: &[Value]) -> String {
+    attachments
+        .iter()
+        .map(|a| {
+            let mut lines: Vec<String> = Vec::new();
+            let pretext = str_of(a, "pretext");
+            if !pretext.is_empty() {
+                lines.push(pretext.to_string());
+            }
+
+            let mut quoted: Vec<String> = Vec::new();
+            let author = str_of(a, "author_name");
+            if !author.is_empty() {
+                quoted.push(format!("_{}_", author));
+            }
+            let (title, title_link) = (str_of(a, "title"), str_of(a, "title_link"));
+            match (title.is_empty(), title_link.is_empty()) {
+                (false, false) => quoted.push(format!("**[{}]({})**", title, title_link)),
+                (false, true) => quoted.push(format!("**{}**", title)),
+                _ => {}
+            }
+            let text = str_of(a, "text");
+            if !text.is_empty() {
+                quoted.extend(text.lines().map(|l| l.to_string()));
+            }
+            for field in a
+                .get("fields")
+                .and_then(|f| f.as_array())
+                .into_iter()
+                .flatten()
+            {
+                quoted.push(format!(
+                    "**{}**: {}",
+                    str_of(field, "title"),
+                    str_of(field, "value")
+                ));
+            }
+            let image = str_of(a, "image_url");
+            if !image.is_empty() {
+                quoted.push(format!("![]({})", image));
+            }
+            let footer = str_of(a, "footer");
+            if !footer.is_empty() {
+                quoted.push(footer.to_string());
+            }
+
+            if quoted.is_empty() && pretext.is_empty() {
+                quoted.push(str_of(a, "fallback").to_string());
+            }
+            lines.extend(quoted.into_iter().map(|l| format!("> {}", l)));
+            lines.join("\n")
+        })
+        .filter(|s| !s.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+// Render Block Kit blocks as Mattermost Markdown
+pub fn render_blocks(blocks: &[Block]) -> String {
+    blocks
+        .iter()
+        .filter_map(|b| match b {
+            Block::RichText { elements } => Some(
+                elements
+                    .iter()
+                    .map(render_rich_text)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            Block::Header { text } => Some(format!("### {}", text.text)),
+            Block::Section { text, fields } => Some(
+                text.iter()
+                    .chain(fields.iter())
+                    .map(render_text)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            Block::Context { elements } => Some(
+                elements
+                    .iter()
+                    .map(render_text)
+                    .filter(|t| !t.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            Block::Divider => Some(String::from("---")),
+            Block::Image {
+                image_url,
+                alt_text,
+            } => Some(format!("![{}]({})", alt_text, image_url)),
+            Block::Unknown => None,
+        })
+        .filter(|s| !s.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn render_text(text: &TextObject) -> String {
+    match text.kind.as_str() {
+        "mrkdwn" => convert_mrkdwn(&text.text),
+        _ => text.text.clone(),
+    }
+}
+
+fn render_rich_text(element: &RichTextElement) -> String {
+    match element {
+        RichTextElement::RichTextSection { elements } => render_inlines(elements),
+        RichTextElement::RichTextList {
+            style,
+            indent,
+            offset,
+            elements,
+        } => elements
+            .iter()
+            .enumerate()
+            .map(|(idx, item)| {
+                let bullet = match style.as_str() {
+                    "ordered" => format!("{}.", offset + idx + 1),
+                    _ => String::from("-"),
+                };
+                format!(
+                    "{}{} {}",
+                    "  ".repeat(*indent),
+                    bullet,
+                    render_rich_text(item)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        RichTextElement::RichTextPreformatted { elements } => {
+            let code: String = elements
+                .iter()
+                .map(|e| match e {
+                    RichTextInline::Text { text, .. } => text.clone(),
+                    RichTextInline::Link { url, text, .. } => text.clone().unwrap_or(url.clone()),
+                    e => render_inline(e),
+                })
+                .collect();
+            format!("```\n{}\n```", code.trim_end_matches('\n'))
+        }
+        RichTextElement::RichTextQuote { elements } => render_inlines(elements)
+            .lines()
+            .map(|l| format!("> {}", l))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        RichTextElement::Unknown => String::new(),
+    }
+}
+
+fn render_inlines(elements: &[RichTextInline]) -> String {
+    elements.iter().map(render_inline).collect()
+}
+
+fn render_inline(element: &RichTextInline) -> String {
+    match element {
+        RichTextInline::Text { text, style } => apply_style(text, style),
+        RichTextInline::Link { url, text, style } => match text {
+            Some(text) if !text.is_empty() => apply_style(&format!("[{}]({})", text, url), style),
+            _ => apply_style(url, style),
+        },
+        // Slack mention syntax is resolved by the Mattermost importer
+        RichTextInline::User { user_id } => format!("<@{}>", user_id),
+        RichTextInline::Channel { channel_id } => format!("<#{}>", channel_id),
+        RichTextInline::Usergroup { usergroup_id } => format!("<!subteam^{}>", usergroup_id),
+        RichTextInline::Broadcast { range } => format!("<!{}>", range),
+        RichTextInline::Emoji { name } => format!(":{}:", name),
+        RichTextInline::Unknown => String::new(),
+    }
+}
+
+// Convert Slack mrkdwn emphasis to Markdown markers used for rich text
+pub fn convert_mrkdwn(text: &str) -> String {
+    MRKDWN_RE
+        .replace_all(text, |caps: &Captures| {
+            if let Some(skip) = caps.name("skip") {
+                return skip.as_str().to_string();
+            }
+            let pre = &caps["pre"];
+            if let Some(bold) = caps.name("bold") {
+                format!("{}**{}**", pre, bold.as_str())
+            } else if let Some(italic) = caps.name("italic") {
+                format!("{}*{}*", pre, italic.as_str())
+            } else {
+                format!("{}~~{}~~", pre, &caps["strike"])
+            }
+        })
+        .into_owned()
+}
+
+// Wrap text into Markdown markers, keeping surrounding whitespace outside
+fn apply_style(text: &str, style: &TextStyle) -> String {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return text.to_string();
+    }
+    let mut styled = trimmed.to_string();
+    if style.code {
+        styled = format!("`{}`", styled);
+    }
+    if style.bold {
+        styled = format!("**{}**", styled);
+    }
+    if style.italic {
+        styled = format!("_{}_", styled);
+    }
+    if style.strike {
+        styled = format!("~~{}~~", styled);
+    }
+    let leading = &text[..text.len() - text.trim_start().len()];
+    let trailing = &text[text.trim_end().len()..];
+    format!("{}{}{}", leading, styled, trailing)
+}
+
+// Render attachments and blocks into post text and props. Blocks are
+// authoritative when present. Attachments go to `props.attachments` when the
+// target renders props, otherwise into the text of empty posts.
+pub fn render_post(post: &mut SlackPost, props: bool) {
+    let attachments: Vec<Value> = post
+        .extra
+        .get("attachments")
+        .and_then(|a| a.as_array())
+        .cloned()
+        .unwrap_or_default();
+    if props && !attachments.is_empty() {
+        post.merge_props(json!({"attachments": to_mattermost_attachments(&attachments)}));
+    }
+
+    let blocks_text = post
+        .blocks()
+        .map(|blocks| render_blocks(&blocks))
+        .unwrap_or_default();
+    if !blocks_text.is_empty() {
+        post.set("text", blocks_text);
+    } else if !props && post.text().unwrap_or_default().trim().is_empty() {
+        let text = render_attachments(&attachments);
+        if !text.is_empty() {
+            post.set("text", text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_attachments() {
+        let attachments = vec![json!({
+            "fallback": "Build passed",
+            "pretext": "CI",
+            "title": "Build #42",
+            "title_link": "https://ci.example.com/42",
+            "text": "All green",
+            "fields": [{"title": "Branch", "value": "main", "short": true}],
+            "callback_id": "ignored"
+        })];
+
+        assert_eq!(
+            render_attachments(&attachments),
+            "CI\n> **[Build #42](https://ci.example.com/42)**\n> All green\n> **Branch**: main"
+        );
+        assert!(to_mattermost_attachments(&attachments)[0]
+            .get("callback_id")
+            .is_none());
+    }
+
+    #[test]
+    fn test_render_blocks() {
+        let blocks: Vec<Block> = serde_json::from_value(json!([
+            {"type": "header", "text": {"type": "plain_text", "text": "Deploy"}},
+            {"type": "section", "text": {"type": "mrkdwn", "text": "*prod* is up"}},
+            {"type": "divider"},
+            {"type": "actions", "elements": []}
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            render_blocks(&blocks),
+            "### Deploy\n\n**prod** is up\n\n---"
+        );
+    }
+
+    #[test]
+    fn test_convert_mrkdwn() {
+        assert_eq!(
+            convert_mrkdwn("*bold* _it_ ~gone~ snake_case_name"),
+            "**bold** *it* ~~gone~~ snake_case_name"
+        );
+        assert_eq!(
+            convert_mrkdwn("<https://x.io/a_b_c|a_b_> `*code*`"),
+            "<https://x.io/a_b_c|a_b_> `*code*`"
+        );
+    }
+
+    #[test]
+    fn test_render_rich_text() {
+        let blocks: Vec<Block> = serde_json::from_value(json!([{
+            "type": "rich_text",
+            "elements": [
+                {"type": "rich_text_section", "elements": [
+                    {"type": "text", "text": "Hey "},
+                    {"type": "user", "user_id": "U1"},
+                    {"type": "text", "text": " see ", "style": {"bold": true}},
+                    {"type": "link", "url": "https://example.com", "text": "docs"},
+                    {"type": "emoji", "name": "tada"}
+                ]},
+                {"type": "rich_text_list", "style": "ordered", "elements": [
+                    {"type": "rich_text_section", "elements": [{"type": "text", "text": "one"}]},
+                    {"type": "rich_text_section", "elements": [
+                        {"type": "text", "text": "two", "style": {"code": true}}
+                    ]}
+                ]},
+                {"type": "rich_text_list", "style": "bullet", "indent": 1, "elements": [
+                    {"type": "rich_text_section", "elements": [{"type": "text", "text": "nested"}]}
+                ]},
+                {"type": "rich_text_preformatted", "elements": [
+                    {"type": "text", "text": "let x = 1;\nlet y = 2;"}
+                ]},
+                {"type": "rich_text_quote", "elements": [{"type": "text", "text": "quoted\nlines"}]}
+            ]
+        }]))
+        .unwrap();
+
+        assert_eq!(
+            render_blocks(&blocks),
+            [
+                "Hey <@U1> **see** [docs](https://example.com):tada:",
+                "1. one",
+                "2. `two`",
+                "  - nested",
+                "```\nlet x = 1;\nlet y = 2;\n```",
+                "> quoted\n> lines",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_render_post_prefers_blocks() {
+        let mut post: SlackPost = serde_json::from_value(json!({
+            "text": "lossy *fallback*",
+            "blocks": [{"type": "rich_text", "elements": [
+                {"type": "rich_text_section", "elements": [
+                    {"type": "text", "text": "rich", "style": {"italic": true}}
+                ]}
+            ]}]
+        }))
+        .unwrap();
+
+        render_post(&mut post, true);
+
+        assert_eq!(post.text(), Some("_rich_"));
+    }
+
+    #[test]
+    fn test_render_post_attachments_once() {
+        let post: SlackPost = serde_json::from_value(json!({
+            "text": "",
+            "attachments": [{"fallback": "Deployed", "color": "good"}]
+        }))
+        .unwrap();
+
+        let mut with_props = post.clone();
+        render_post(&mut with_props, true);
+        assert_eq!(with_props.text(), Some(""));
+        assert_eq!(
+            with_props.extra["props"]["attachments"][0]["color"],
+            json!("good")
+        );
+
+        let mut as_text = post;
+        render_post(&mut as_text, false);
+        assert_eq!(as_text.text(), Some("> Deployed"));
+        assert!(as_text.extra.get("props").is_none());
+    }
+}
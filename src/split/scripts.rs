@@ -0,0 +1,243 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+// Import driver scripts written next to the chunks. Archives are imported
+// in dependency order: emoji, directs, then channels.
+#[derive(Debug, Default)]
+pub struct ImportScripts {
+    // Default team, the first script argument overrides it
+    pub team: Option<String>,
+    // Mattermost bulk import archive
+    pub emoji: Option<String>,
+    pub directs: Vec<ImportArchive>,
+    pub channels: Vec<ImportArchive>,
+    // Mattermost usernames to demote after import
+    pub guests: Vec<String>,
+    // Workspace and name of channels archived in Slack
+    pub archived_channels: Vec<(String, String)>,
+}
+
+// Chunk archive, the workspace and the team it is imported into
+#[derive(Debug, Default, Clone)]
+pub struct ImportArchive {
+    pub name: String,
+    pub workspace: String,
+    pub team: Option<String>,
+}
+
+impl ImportScripts {
+    // `mattermost` server CLI, run on the server host. Bulk import reads
+    // JSONL, so the emoji archive is unpacked first
+    pub fn bash(&self) -> String {
+        let mut out = self.header();
+        if let Some(emoji) = &self.emoji {
+            let _ = writeln!(
+                out,
+                "EMOJI=$(mktemp -d)\nunzip -q \"$DIR/{}\" -d \"$EMOJI\"\nmattermost import bulk \"$EMOJI/import.jsonl\" --import-path \"$EMOJI/data\" --apply\nrm -rf \"$EMOJI\"",
+                emoji
+            );
+        }
+        for archive in self.directs.iter().chain(self.channels.iter()) {
+            let _ = writeln!(
+                out,
+                "mattermost import slack \"${}\" \"$DIR/{}\"",
+                team_variable(&archive.workspace),
+                archive.name
+            );
+        }
+        for guest in self.guests.iter() {
+            let _ = writeln!(out, "mattermost user demote \"{}\"", guest);
+        }
+        for (workspace, channel) in self.archived_channels.iter() {
+            let _ = writeln!(
+                out,
+                "mattermost channel archive \"${}:{}\"",
+                team_variable(workspace),
+                channel
+            );
+        }
+        out
+    }
+
+    // `mmctl` through the API. Slack chunks are converted to bulk import
+    // format with `mmetl` first, then each archive is uploaded and its
+    // import job is polled until it finishes
+    pub fn mmctl(&self) -> String {
+        let mut out = self.header();
+        out.push_str(MMCTL_IMPORT);
+        if let Some(emoji) = &self.emoji {
+            let _ = writeln!(out, "import_bulk \"$DIR/{}\"", emoji);
+        }
+        for archive in self.directs.iter().chain(self.channels.iter()) {
+            let _ = writeln!(
+                out,
+                "import_slack \"$DIR/{}\" \"${}\"",
+                archive.name,
+                team_variable(&archive.workspace)
+            );
+        }
+        for guest in self.guests.iter() {
+            let _ = writeln!(out, "mmctl user demote \"{}\"", guest);
+        }
+        for (workspace, channel) in self.archived_channels.iter() {
+            let _ = writeln!(
+                out,
+                "mmctl channel archive \"${}:{}\"",
+                team_variable(workspace),
+                channel
+            );
+        }
+        out
+    }
+
+    // Team parameters: the first argument for regular exports and DMs, an
+    // environment variable per Enterprise Grid workspace
+    fn header(&self) -> String {
+        let mut out = String::from("#!/bin/bash\nset -euo pipefail\n\n");
+        let _ = writeln!(
+            out,
+            "TEAM=\"${{1:-{}}}\"",
+            self.team.as_deref().unwrap_or_default()
+        );
+        let mut workspaces: BTreeMap<&str, Option<&str>> = BTreeMap::new();
+        for archive in self.directs.iter().chain(self.channels.iter()) {
+            if !archive.workspace.is_empty() {
+                let team = workspaces.entry(&archive.workspace).or_default();
+                *team = team.or(archive.team.as_deref());
+            }
+        }
+        for (workspace, _) in self.archived_channels.iter() {
+            if !workspace.is_empty() {
+                workspaces.entry(workspace).or_default();
+            }
+        }
+        let mut variables = vec![String::from("TEAM")];
+        for (workspace, team) in workspaces {
+            let variable = team_variable(workspace);
+            let _ = writeln!(
+                out,
+                "{}=\"${{{}:-{}}}\"",
+                variable,
+                variable,
+                team.unwrap_or("$TEAM")
+            );
+            variables.push(variable);
+        }
+        let _ = writeln!(
+            out,
+            "for team in {}; do\n    if [ -z \"$team\" ]; then\n        echo \"Usage: [TEAM_<WORKSPACE>=team ...] $0 TEAM\" >&2\n        exit 1\n    fi\ndone",
+            variables
+                .iter()
+                .map(|v| format!("\"${}\"", v))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        out.push_str("DIR=\"$(cd \"$(dirname \"$0\")\" && pwd)\"\n\n");
+        out
+    }
+}
+
+// Shell variable with the team of the workspace
+fn team_variable(workspace: &str) -> String {
+    if workspace.is_empty() {
+        return String::from("TEAM");
+    }
+    let name: String = workspace
+        .to_uppercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("TEAM_{}", name)
+}
+
+// `import_bulk` is not named `import`, which would shadow ImageMagick
+const MMCTL_IMPORT: &str = r#"import_bulk() {
+    local file="$1"
+    local name
+    echo "Upload $file"
+    # Uploaded file is named after the upload session
+    name=$(mmctl import upload "$file" --json | jq -s -r '.[0] | "\(.id)_\(.filename)"')
+    local job
+    job=$(mmctl import process "$name" --json | jq -r '.id')
+    while true; do
+        local status
+        status=$(mmctl import job show "$job" --json | jq -r '.status')
+        case "$status" in
+            success) echo "Imported $file"; break ;;
+            error|canceled) echo "Import of $file failed: $status" >&2; exit 1 ;;
+        esac
+        sleep 10
+    done
+}
+
+import_slack() {
+    local file="$1"
+    local team="$2"
+    local work
+    work=$(mktemp -d)
+    echo "Transform $file"
+    (cd "$work" && mmetl transform slack --team "$team" --file "$file" \
+        --output import.jsonl --attachments-dir data && zip -qr bulk.zip import.jsonl data)
+    import_bulk "$work/bulk.zip"
+    rm -rf "$work"
+}
+
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_order() {
+        let scripts = ImportScripts {
+            team: Some("acme".to_string()),
+            emoji: Some("emoji_export.zip".to_string()),
+            directs: vec![ImportArchive {
+                name: "directs_000_export.zip".to_string(),
+                ..Default::default()
+            }],
+            channels: vec![
+                ImportArchive {
+                    name: "channels_000_export.zip".to_string(),
+                    ..Default::default()
+                },
+                ImportArchive {
+                    name: "channels_sales_000_export.zip".to_string(),
+                    workspace: "sales".to_string(),
+                    team: Some("acme-sales".to_string()),
+                },
+            ],
+            guests: vec!["carol".to_string()],
+            archived_channels: vec![
+                (String::new(), "old".to_string()),
+                ("support-eu".to_string(), "legacy".to_string()),
+            ],
+        };
+
+        let bash = scripts.bash();
+        assert!(bash.contains("TEAM=\"${1:-acme}\"\n"));
+        assert!(bash.contains("TEAM_SALES=\"${TEAM_SALES:-acme-sales}\"\n"));
+        assert!(bash.contains("TEAM_SUPPORT_EU=\"${TEAM_SUPPORT_EU:-$TEAM}\"\n"));
+        assert!(bash.contains("for team in \"$TEAM\" \"$TEAM_SALES\" \"$TEAM_SUPPORT_EU\"; do"));
+        assert!(bash.contains("unzip -q \"$DIR/emoji_export.zip\" -d \"$EMOJI\"\n"));
+        assert!(bash.contains(
+            "mattermost import bulk \"$EMOJI/import.jsonl\" --import-path \"$EMOJI/data\" --apply\n"
+        ));
+        let emoji = bash.find("emoji_export.zip").unwrap();
+        let directs = bash.find("directs_000").unwrap();
+        let channels = bash.find("channels_000").unwrap();
+        assert!(emoji < directs && directs < channels);
+        assert!(bash.contains(
+            "mattermost import slack \"$TEAM_SALES\" \"$DIR/channels_sales_000_export.zip\"\n"
+        ));
+        assert!(bash.contains("mattermost user demote \"carol\"\n"));
+        assert!(bash.ends_with("mattermost channel archive \"$TEAM_SUPPORT_EU:legacy\"\n"));
+
+        let mmctl = scripts.mmctl();
+        assert!(mmctl.contains("\nimport_bulk \"$DIR/emoji_export.zip\"\n"));
+        assert!(!mmctl.contains("\nimport() {"));
+        assert!(mmctl.contains("import_slack \"$DIR/directs_000_export.zip\" \"$TEAM\"\n"));
+        assert!(mmctl.contains("mmctl user demote \"carol\"\n"));
+        assert!(mmctl.contains("mmctl channel archive \"$TEAM:old\"\n"));
+    }
+}
@@ -1,11 +1,16 @@
-use std::{collections::HashMap, ffi::OsStr, path::PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    ffi::OsStr,
+    path::PathBuf,
+};
 
 use anyhow::{anyhow, bail, Error, Result};
 use async_zip::{
     base::{read::seek::ZipFileReader, write::ZipFileWriter},
     Compression, ZipEntryBuilder, ZipString,
 };
-use futures::{AsyncReadExt, SinkExt, StreamExt};
+use chrono_tz::Tz;
+use futures::{AsyncReadExt, StreamExt};
 use indicatif::{ProgressBar, ProgressStyle};
 use log::{error, info, warn};
 use tokio::{
@@ -19,25 +24,38 @@ use tokio_util::{
 use trauma::{download::Download, downloader::DownloaderBuilder};
 
 use super::{
-    model::{self, Chunk, ChunkItem, Direct},
+    bots,
+    delta::State,
+    emoji::{self, Emoji},
+    external::{self, ExternalUser},
+    extract, fallbacks,
+    grid::{dir_of, split_workspace, workspace_path},
+    guests::{GuestPolicy, Guests},
+    history::{apply_history, History},
+    inspect::{ChannelKind, ChannelStats, Report},
+    model::{self, url_extension, Channel, Chunk, ChunkItem, Direct, SlackPost, User},
+    plan::{self, Plan, PlannedChunk},
+    render,
+    scripts::{ImportArchive, ImportScripts},
+    subtypes::{self, SubtypeAction},
+    transcript::Transcript,
+    users::{self, DeletedUsersPolicy, UserMapping, FORMER_USER_ID},
+    validate::{self, Category, Problems},
     Config,
 };
 
 const BUF_SIZE: usize = 65536;
 
-static SHARED_NAMES: &[&str] = &[
-    "users.json",
-    "channels.json",
-    "dms.json",
-    "groups.json",
-    "mpims.json",
-];
+static CHANNEL_NAMES: &[&str] = &["channels.json", "groups.json", "mpims.json"];
 
 pub struct Splitter {
     config: Config,
     reader: ZipFileReader<Compat<BufReader<File>>>,
 
+    // Files outside channel directories: users.json, channels.json, ...
     shared_files_idx: HashMap<String, usize>,
+    // Enterprise Grid workspaces, empty name for org-wide and regular exports
+    workspaces: BTreeSet<String>,
     // List of chunks with file path:idx mapping
     chunked_files_idx: Vec<Chunk>,
     // files_idx: HashMap<String, usize>,
@@ -49,6 +67,32 @@ pub struct Splitter {
     direct_files_idx: HashMap<String, Vec<(String, usize)>>,
     chunked_directs_idx: Vec<Chunk>,
 
+    emoji: Vec<Emoji>,
+
+    // Channels by shared file path: channels.json, groups.json, mpims.json
+    channels: BTreeMap<String, Vec<Channel>>,
+    // Pinned messages ts by channel directory path
+    pins: HashMap<String, HashSet<String>>,
+    // Number of posts by subtype
+    subtype_counts: BTreeMap<String, usize>,
+
+    users: Vec<User>,
+    user_mapping: UserMapping,
+    // Slack id of the user receiving bot messages
+    bot_user: Option<String>,
+    // Names from previous mapping and `--user-map` for users found in posts
+    fixed_names: HashMap<String, String>,
+    // Slack Connect authors missing from users.json
+    external_users: BTreeMap<String, ExternalUser>,
+    guests: Guests,
+    // State of the previous run with `--since` and of the current one
+    since: Option<State>,
+    state: State,
+    // Written archives in import order
+    scripts: ImportScripts,
+    // Avatars are bundled into the first exported chunk only
+    avatar_downloads: Vec<Download>,
+
     pb: ProgressBar,
 }
 
@@ -65,30 +109,1170 @@ impl Splitter {
             config,
             pb,
             shared_files_idx: HashMap::new(),
+            workspaces: BTreeSet::new(),
             chunked_files_idx: Vec::new(),
             // files_idx: HashMap::new(),
             directs: Vec::new(),
             grouped_files_idx: HashMap::new(),
             direct_files_idx: HashMap::new(),
             chunked_directs_idx: Vec::new(),
+            emoji: Vec::new(),
+            channels: BTreeMap::new(),
+            pins: HashMap::new(),
+            subtype_counts: BTreeMap::new(),
+            users: Vec::new(),
+            user_mapping: UserMapping::default(),
+            bot_user: None,
+            fixed_names: HashMap::new(),
+            external_users: BTreeMap::new(),
+            guests: Guests::default(),
+            since: None,
+            state: State::default(),
+            scripts: ImportScripts::default(),
+            avatar_downloads: Vec::new(),
         })
     }
 
-    pub async fn split(&mut self) -> Result<()> {
-        info!("Sptit..");
-        self.scan_files().await?;
-        if !self.config.skip_directs {
-            self.fetch_directs().await?;
-            self.split_directs_to_chunks();
-            self.export_directs_chunks().await?;
+    pub async fn split(&mut self) -> Result<()> {
+        info!("Sptit..");
+        tokio::fs::create_dir_all(&self.config.output).await?;
+        self.prepare().await?;
+        if let Some(path) = self.config.since.clone() {
+            self.apply_since(State::read(&path)?);
+        }
+        match self.config.plan.clone() {
+            Some(path) => self.apply_plan(&Plan::read(&path)?)?,
+            None => {
+                self.split_directs_to_chunks();
+                self.split_files_to_chunks();
+            }
+        }
+        let mut exported = Vec::new();
+        if !self.config.skip_directs {
+            exported.extend(self.chunked_directs_idx.clone());
+        }
+        if !self.config.skip_channels {
+            exported.extend(self.chunked_files_idx.clone());
+        }
+        self.scan_external_users(&exported).await?;
+        self.fetch_emoji().await?;
+        if !self.emoji.is_empty() {
+            self.export_emoji_chunk().await?;
+        }
+        if !self.config.skip_directs {
+            self.export_directs_chunks().await?;
+        }
+        if !self.config.skip_channels {
+            self.export_channels_chunks().await?;
+        }
+        self.write_reports().await?;
+        self.print_summary();
+        Ok(())
+    }
+
+    // Read archive structure, users and conversations listings
+    async fn prepare(&mut self) -> Result<()> {
+        self.scan_files().await?;
+        self.fetch_users().await?;
+        self.map_users().await?;
+        if !self.config.skip_directs {
+            self.fetch_directs().await?;
+        }
+        if !self.config.skip_channels {
+            self.fetch_channels().await?;
+            self.apply_channel_updates().await?;
+        }
+        Ok(())
+    }
+
+    // Apply header/purpose system posts to channels before any chunk is
+    // exported, every chunk gets the final channel listings
+    async fn apply_channel_updates(&mut self) -> Result<()> {
+        if !self.config.subtype_policy.has_updates() {
+            return Ok(());
+        }
+        let mut dirs: HashMap<String, (String, usize)> = HashMap::new();
+        for (listing, channels) in self.channels.iter() {
+            let workspace = split_workspace(listing).0;
+            for (idx, channel) in channels.iter().enumerate() {
+                dirs.insert(
+                    workspace_path(workspace, &channel.name),
+                    (listing.clone(), idx),
+                );
+            }
+        }
+
+        let mut updated = 0;
+        for (dir, (listing, idx)) in dirs {
+            let Some(mut files) = self.grouped_files_idx.get(&dir).cloned() else {
+                continue;
+            };
+            files.sort();
+            for (_, entry) in files {
+                let buffer = self.read_entry(entry).await?;
+                let Ok(posts) = serde_json::from_slice::<Vec<SlackPost>>(&buffer) else {
+                    continue;
+                };
+                for post in posts.iter() {
+                    let Some(subtype) = post.str_field("subtype") else {
+                        continue;
+                    };
+                    if self.config.subtype_policy.action(subtype) == SubtypeAction::Update {
+                        let channel = &mut self.channels.get_mut(&listing).unwrap()[idx];
+                        subtypes::apply_update(post, channel);
+                        updated += 1;
+                    }
+                }
+            }
+            let channel = &mut self.channels.get_mut(&listing).unwrap()[idx];
+            if channel.truncate_texts() {
+                warn!(
+                    "Topic or purpose of channel {} is truncated to Mattermost limits",
+                    channel.name
+                );
+            }
+        }
+        info!("Applied {} channel header and purpose updates", updated);
+        Ok(())
+    }
+
+    // Build chunk layout without writing archives
+    pub async fn plan(&mut self) -> Result<Plan> {
+        self.prepare().await?;
+        self.split_directs_to_chunks();
+        self.split_files_to_chunks();
+        let kinds = self.channel_kinds().await?;
+        let with_files = !self.config.skip_downloading;
+
+        let mut plan = Plan {
+            archive: self.archive_name("export.zip"),
+            num_chunks: self.config.num_chunks,
+            ..Default::default()
+        };
+        for (prefix, chunks) in [
+            ("directs", self.chunked_directs_idx.clone()),
+            ("channels", self.chunked_files_idx.clone()),
+        ] {
+            let names = plan::chunk_names(&chunks, prefix, &plan.archive);
+            for (name, chunk) in names.into_iter().zip(chunks) {
+                let mut channels = Vec::with_capacity(chunk.items.len());
+                for item in chunk.items {
+                    let kind = kinds.get(&item.id).copied().unwrap_or_default();
+                    channels.push(self.channel_stats(&item.id, kind, item.files).await?);
+                }
+                let mut planned = PlannedChunk::new(name, chunk.workspace, channels, with_files);
+                planned.team = chunk.team;
+                match prefix {
+                    "directs" => plan.directs.push(planned),
+                    _ => plan.channels.push(planned),
+                }
+            }
+        }
+        Ok(plan)
+    }
+
+    // Use chunk layout from the plan, conversations missing from the plan
+    // are split to additional chunks
+    fn apply_plan(&mut self, plan: &Plan) -> Result<()> {
+        info!("Use chunk layout from plan of {}", plan.archive);
+        if plan.archive != self.archive_name("export.zip") {
+            warn!(
+                "Plan was made for {}, not {}",
+                plan.archive,
+                self.archive_name("export.zip")
+            );
+        }
+
+        let mut directs = self.direct_files_idx.clone();
+        let mut channels = self.grouped_files_idx.clone();
+        for planned in plan.directs.iter() {
+            let (mut chunk, missing) = planned.to_chunk(&mut directs);
+            chunk.team = chunk.team.or(self.config.teams.get("").map(String::from));
+            for name in missing {
+                warn!("Planned {} is not found in the archive", name);
+            }
+            self.chunked_directs_idx.push(chunk);
+        }
+        for planned in plan.channels.iter() {
+            let (mut chunk, missing) = planned.to_chunk(&mut channels);
+            let team = self.config.teams.get(&chunk.workspace).map(String::from);
+            chunk.team = chunk.team.or(team);
+            for name in missing {
+                warn!("Planned {} is not found in the archive", name);
+            }
+            self.chunked_files_idx.push(chunk);
+        }
+        // Archives are named by position, so the plan must keep the names
+        // made by `chunk_names` for its own order
+        for (prefix, planned, chunks) in [
+            ("directs", &plan.directs, &self.chunked_directs_idx),
+            ("channels", &plan.channels, &self.chunked_files_idx),
+        ] {
+            let names = plan::chunk_names(chunks, prefix, &plan.archive);
+            for (planned, name) in planned.iter().zip(names) {
+                if planned.name != name {
+                    bail!(
+                        "Planned chunk {} would be exported as {}, chunks must stay numbered per workspace in plan order",
+                        planned.name,
+                        name
+                    );
+                }
+            }
+        }
+
+        let mut leftovers: Vec<String> = directs.into_keys().collect();
+        leftovers.sort();
+        if !leftovers.is_empty() {
+            warn!("{} directs are not in the plan", leftovers.len());
+            self.chunk_directs(leftovers);
+        }
+        for workspace in self.workspaces.clone() {
+            let mut leftovers: Vec<String> = channels
+                .keys()
+                .filter(|k| split_workspace(k).0 == workspace)
+                .cloned()
+                .collect();
+            leftovers.sort();
+            if !leftovers.is_empty() {
+                warn!("{} channels are not in the plan", leftovers.len());
+                self.split_workspace_to_chunks(&workspace, leftovers);
+            }
+        }
+        Ok(())
+    }
+
+    // Export the given channels and direct conversations as one archive,
+    // listings are limited to the extracted conversations
+    pub async fn extract(&mut self, channels: &[String], dms: &[String]) -> Result<()> {
+        tokio::fs::create_dir_all(&self.config.output).await?;
+        self.prepare().await?;
+
+        let mut listings: BTreeMap<&str, Vec<Channel>> = CHANNEL_NAMES
+            .iter()
+            .map(|name| (*name, Vec::new()))
+            .collect();
+        let mut directs: Vec<Direct> = Vec::new();
+        let mut chunk = Chunk::default();
+        // Channel names of different workspaces collide in one archive,
+        // org-wide conversations go with any workspace
+        let mut use_workspace = |key: &str, workspace: &str| -> Result<()> {
+            if workspace.is_empty() || chunk.workspace == workspace {
+                return Ok(());
+            }
+            if !chunk.workspace.is_empty() {
+                bail!(
+                    "{} is in workspace {}, an extract is limited to workspace {}",
+                    key,
+                    workspace,
+                    chunk.workspace
+                );
+            }
+            chunk.workspace = workspace.to_string();
+            Ok(())
+        };
+        let mut items: Vec<ChunkItem> = Vec::new();
+        for key in channels {
+            let Some((listing, channel)) = extract::find_channel(&self.channels, key)? else {
+                bail!("Channel {} is not found", key);
+            };
+            let (workspace, filename) = split_workspace(listing);
+            use_workspace(key, workspace)?;
+            let dir = workspace_path(workspace, &channel.name);
+            if let Some(channels) = listings.get_mut(filename) {
+                channels.push(channel.clone());
+            }
+            items.push(ChunkItem {
+                files: self
+                    .grouped_files_idx
+                    .get(&dir)
+                    .cloned()
+                    .unwrap_or_default(),
+                id: dir,
+            });
+        }
+        for key in dms {
+            let mut members = BTreeSet::new();
+            for user in key.split(',') {
+                members.insert(self.user_id(user.trim())?);
+            }
+            if let Some(dm) = extract::find_direct(&self.directs, &members) {
+                items.push(ChunkItem {
+                    id: dm.id.clone(),
+                    files: self
+                        .direct_files_idx
+                        .get(&dm.id)
+                        .cloned()
+                        .unwrap_or_default(),
+                });
+                directs.push(dm.clone());
+            } else if let Some((listing, group)) = extract::find_group(&self.channels, &members) {
+                let workspace = split_workspace(listing).0;
+                use_workspace(key, workspace)?;
+                let dir = workspace_path(workspace, &group.name);
+                items.push(ChunkItem {
+                    files: self
+                        .grouped_files_idx
+                        .get(&dir)
+                        .cloned()
+                        .unwrap_or_default(),
+                    id: dir,
+                });
+                listings
+                    .entry("mpims.json")
+                    .or_default()
+                    .push(group.clone());
+            } else {
+                bail!("Direct conversation of {} is not found", key);
+            }
+        }
+
+        chunk.items = items;
+        let mut data: Vec<(String, Vec<u8>)> =
+            vec![("dms.json".to_string(), serde_json::to_vec(&directs)?)];
+        for (filename, channels) in listings {
+            data.push((filename.to_string(), serde_json::to_vec(&channels)?));
+        }
+        let additional_data: HashMap<String, &[u8]> = data
+            .iter()
+            .map(|(filename, data)| (filename.clone(), data.as_slice()))
+            .collect();
+
+        let output = self
+            .config
+            .output
+            .join(format!("extract_{}", self.archive_name("export.zip")));
+        info!("Extract {} conversations: {:?}", chunk.items.len(), output);
+        self.scan_external_users(std::slice::from_ref(&chunk))
+            .await?;
+        self.export_chunk(output, chunk, vec!["users.json"], &additional_data)
+            .await
+    }
+
+    // Render every conversation into `transcripts/` as HTML and optionally
+    // plain text, attachments are downloaded to `__uploads/` next to them
+    pub async fn transcript(&mut self, timezone: Tz, text: bool) -> Result<()> {
+        self.prepare().await?;
+        let names: HashMap<String, String> = self
+            .users
+            .iter()
+            .map(|u| (u.id.clone(), u.name.clone()))
+            .collect();
+        let members = |ids: &[String]| {
+            ids.iter()
+                .map(|id| names.get(id).unwrap_or(id).as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let mut titles: HashMap<String, String> = HashMap::new();
+        for (listing, channels) in self.channels.iter() {
+            let (workspace, filename) = split_workspace(listing);
+            for channel in channels {
+                let title = match filename {
+                    "mpims.json" => format!("Group DM: {}", members(&channel.members)),
+                    _ => format!("#{}", channel.name),
+                };
+                titles.insert(workspace_path(workspace, &channel.name), title);
+            }
+        }
+        for dm in self.directs.iter() {
+            titles.insert(dm.id.clone(), format!("DM: {}", members(&dm.members)));
+        }
+
+        let mut dirs: Vec<(String, Vec<(String, usize)>)> = self
+            .grouped_files_idx
+            .clone()
+            .into_iter()
+            .chain(self.direct_files_idx.clone())
+            .collect();
+        dirs.sort();
+        let root = self.config.output.join("transcripts");
+        let mut downloads: Vec<Download> = Vec::new();
+        for (dir, mut files) in dirs {
+            files.sort();
+            // Same history and subtype handling as transform
+            let history = self.conversation_history(&files).await?;
+            let mut posts: Vec<SlackPost> = Vec::new();
+            for (filename, idx) in files {
+                let buffer = self.read_entry(idx).await?;
+                match serde_json::from_slice::<Vec<SlackPost>>(&buffer) {
+                    Ok(day) => {
+                        let day = apply_history(
+                            day,
+                            &history,
+                            self.config.preserve_edits,
+                            self.config.deleted_messages,
+                        );
+                        posts.extend(self.apply_subtype_policy(day));
+                    }
+                    Err(e) => warn!("Skip {} in transcript, cannot parse it: {}", filename, e),
+                }
+            }
+            for post in posts.iter_mut() {
+                if bots::is_bot_message(post) {
+                    bots::convert_bot_message(post, self.bot_user.as_deref());
+                }
+                render::render_post(post, false);
+                if let Some(file) = post.file.take() {
+                    post.files.push(file);
+                }
+                fallbacks::apply_fallbacks(post);
+                // Rendered blocks and huddle summaries carry the original user ids
+                self.user_mapping.apply_post(post);
+                let files = post.files.clone();
+                self.push_to_download(&files, &mut downloads).await?;
+            }
+
+            // Uploads are relative to the transcript, Grid ones are nested
+            let uploads = format!("{}__uploads", "../".repeat(dir.matches('/').count() + 1));
+            let transcript = Transcript::new(
+                titles.get(&dir).cloned().unwrap_or_else(|| dir.clone()),
+                posts,
+                &names,
+                timezone,
+                (!self.config.skip_downloading).then_some(uploads.as_str()),
+            );
+            let path = root.join(&dir);
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(path.with_extension("html"), transcript.to_html()).await?;
+            if text {
+                tokio::fs::write(path.with_extension("txt"), transcript.to_text()).await?;
+            }
+        }
+        info!("Transcripts: {:?}", root);
+
+        if self.config.skip_downloading {
+            warn!("Skip downloading attachments, transcripts link to Slack!!!");
+        } else {
+            self.download(&downloads).await;
+        }
+        Ok(())
+    }
+
+    // Canonical id of the user by Slack id or name
+    fn user_id(&self, key: &str) -> Result<String> {
+        let key = key.trim_start_matches('@');
+        match self.users.iter().find(|u| u.id == key || u.name == key) {
+            Some(user) => Ok(self.user_mapping.canonical_id(&user.id).to_string()),
+            None => bail!("User {} is not found", key),
+        }
+    }
+
+    // Keep only day files which may have posts newer than the previous run,
+    // listings stay complete as posts reference users and channels by id
+    fn apply_since(&mut self, previous: State) {
+        info!("Export changes since the run on {}", previous.archive);
+        let new_users = self
+            .users
+            .iter()
+            .filter(|u| !previous.users.contains(&u.id))
+            .count();
+        let new_channels = self.channel_ids().difference(&previous.channels).count();
+        info!("New users: {}, new channels: {}", new_users, new_channels);
+
+        for files_idx in [&mut self.grouped_files_idx, &mut self.direct_files_idx] {
+            for (key, files) in files_idx.iter_mut() {
+                let total = files.len();
+                files.retain(|(filename, _)| previous.is_new_day(filename));
+                if files.len() < total {
+                    info!(
+                        "{}: {} of {} days exported before are skipped",
+                        key,
+                        total - files.len(),
+                        total
+                    );
+                }
+            }
+            files_idx.retain(|_, files| !files.is_empty());
+        }
+        info!(
+            "Conversations with new days: {}",
+            self.grouped_files_idx.len()
+        );
+        // Conversations without new posts keep their progress
+        self.state.conversations = previous.conversations.clone();
+        self.since = Some(previous);
+    }
+
+    fn channel_ids(&self) -> BTreeSet<String> {
+        self.channels
+            .values()
+            .flatten()
+            .map(|c| c.id.clone())
+            .chain(self.directs.iter().map(|d| d.id.clone()))
+            .collect()
+    }
+
+    fn archive_name(&self, default: &str) -> String {
+        self.config
+            .slack_archive
+            .file_name()
+            .unwrap_or(OsStr::new(default))
+            .to_str()
+            .unwrap_or(default)
+            .to_string()
+    }
+
+    // User mapping is written after all chunks, external users are found in posts
+    async fn write_reports(&mut self) -> Result<()> {
+        tokio::fs::write(
+            self.config.output.join("user_mapping.csv"),
+            self.user_mapping.to_csv()?,
+        )
+        .await?;
+
+        if !self.guests.guests.is_empty() {
+            tokio::fs::write(self.config.output.join("guests.csv"), self.guests.to_csv()?).await?;
+        }
+
+        if !self.external_users.is_empty() {
+            let path = self.config.output.join("external_users.csv");
+            warn!(
+                "Found {} external users, see {:?}",
+                self.external_users.len(),
+                path
+            );
+            tokio::fs::write(path, external::to_csv(self.external_users.values())?).await?;
+        }
+
+        self.state.archive = self.archive_name("export.zip");
+        self.state.users = self.users.iter().map(|u| u.id.clone()).collect();
+        self.state.channels = self.channel_ids();
+        self.state.since = self.since.as_ref().map(|s| s.conversations.clone());
+        self.state.plan = self
+            .config
+            .plan
+            .as_ref()
+            .map(|p| p.to_string_lossy().to_string());
+        tokio::fs::write(
+            self.config.output.join("state.json"),
+            serde_json::to_vec_pretty(&self.state)?,
+        )
+        .await?;
+
+        self.scripts.team = self.config.teams.get("").map(String::from);
+        self.scripts.guests = self
+            .guests
+            .guests
+            .values()
+            .map(|g| g.username.clone())
+            .collect();
+        self.scripts.archived_channels = self
+            .channels
+            .iter()
+            .flat_map(|(listing, channels)| {
+                let workspace = split_workspace(listing).0;
+                channels
+                    .iter()
+                    .filter(|c| c.is_archived)
+                    .map(move |c| (workspace.to_string(), c.name.clone()))
+            })
+            .collect();
+        for (filename, script) in [
+            ("import.sh", self.scripts.bash()),
+            ("import_mmctl.sh", self.scripts.mmctl()),
+        ] {
+            let path = self.config.output.join(filename);
+            tokio::fs::write(&path, script).await?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                tokio::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).await?;
+            }
+        }
+        info!("Import scripts: {:?}", self.config.output.join("import.sh"));
+        Ok(())
+    }
+
+    // Collect archive statistics without writing anything
+    pub async fn inspect(&mut self) -> Result<Report> {
+        self.scan_files().await?;
+        self.fetch_users().await?;
+        let kinds = self.channel_kinds().await?;
+
+        let mut dirs: Vec<String> = self.grouped_files_idx.keys().cloned().collect();
+        dirs.sort();
+        let mut channels = Vec::with_capacity(dirs.len());
+        for dir in dirs {
+            let kind = kinds.get(&dir).copied().unwrap_or_default();
+            let files = self.grouped_files_idx[&dir].clone();
+            channels.push(self.channel_stats(&dir, kind, files).await?);
+        }
+
+        let archive = self.config.slack_archive.to_string_lossy().to_string();
+        Ok(Report::new(archive, self.users.len(), channels))
+    }
+
+    // Check generated chunks against the source archive, the config must
+    // match the one used for transform
+    pub async fn verify(&mut self) -> Result<Problems> {
+        self.prepare().await?;
+        let archive = self.archive_name("export.zip");
+        let mut problems = Problems::default();
+
+        // Incremental runs export only posts newer than their previous run
+        let state_path = self.config.output.join("state.json");
+        if let Some(previous) = State::read(&state_path).ok().and_then(|s| s.previous()) {
+            self.apply_since(previous);
+        }
+
+        // Day file path as exported to its index in the source archive and
+        // its conversation
+        let mut conversations: HashMap<String, Vec<(String, usize)>> = HashMap::new();
+        if !self.config.skip_directs {
+            conversations.extend(self.direct_files_idx.clone());
+        }
+        if !self.config.skip_channels {
+            conversations.extend(self.grouped_files_idx.clone());
+        }
+        let mut expected: BTreeMap<String, (usize, String)> = BTreeMap::new();
+        for (dir, files) in conversations.iter() {
+            for (source, idx) in files {
+                expected.insert(source.clone(), (*idx, dir.clone()));
+            }
+        }
+        let mut histories: HashMap<String, History> = HashMap::new();
+
+        let mut chunks: Vec<(String, String, String)> = Vec::new();
+        let mut read_dir = tokio::fs::read_dir(&self.config.output).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if let Some((prefix, workspace)) = plan::parse_chunk_name(&name, &archive) {
+                chunks.push((name.clone(), prefix.to_string(), workspace.to_string()));
+            }
+        }
+        chunks.sort();
+        if chunks.is_empty() {
+            problems.error(
+                Category::Missing,
+                &self.config.output.to_string_lossy(),
+                format!("no chunks of {} found", archive),
+            );
+        }
+
+        let mut seen: BTreeMap<String, usize> = BTreeMap::new();
+        for (name, prefix, workspace) in chunks {
+            let path = self.config.output.join(&name);
+            let mut reader =
+                match ZipFileReader::with_tokio(BufReader::new(File::open(&path).await?)).await {
+                    Ok(reader) => reader,
+                    Err(e) => {
+                        problems.error(Category::Json, &name, format!("cannot open chunk: {}", e));
+                        continue;
+                    }
+                };
+            let entries: Vec<String> = reader
+                .file()
+                .entries()
+                .iter()
+                .filter_map(|e| e.filename().clone().into_string().ok())
+                .collect();
+            let entry_names: HashSet<&str> = entries.iter().map(|e| e.as_str()).collect();
+
+            // Listings the chunk is exported with, Grid workspaces and the
+            // organization root have different ones
+            let mut listings = vec!["users.json"];
+            match prefix.as_str() {
+                "directs" => listings.push("dms.json"),
+                _ => listings.extend(
+                    CHANNEL_NAMES
+                        .iter()
+                        .filter(|name| self.has_listing(&workspace, name)),
+                ),
+            }
+            for shared in listings {
+                if !entry_names.contains(shared) {
+                    problems.error(Category::Missing, &name, format!("{} is missing", shared));
+                }
+            }
+
+            for (idx, entry) in entries.iter().enumerate() {
+                if !entry.contains('/') || entry.starts_with("__uploads/") {
+                    continue;
+                }
+                let source = workspace_path(&workspace, entry);
+                *seen.entry(source.clone()).or_default() += 1;
+
+                let mut buffer = Vec::new();
+                reader
+                    .reader_with_entry(idx)
+                    .await?
+                    .read_to_end(&mut buffer)
+                    .await?;
+                let posts: Vec<SlackPost> = match serde_json::from_slice(&buffer) {
+                    Ok(posts) => posts,
+                    Err(e) => {
+                        problems.error(
+                            Category::Json,
+                            &format!("{}:{}", name, entry),
+                            e.to_string(),
+                        );
+                        continue;
+                    }
+                };
+
+                if let Some((source_idx, dir)) = expected.get(&source) {
+                    let count = self
+                        .expected_posts(
+                            &source,
+                            *source_idx,
+                            dir,
+                            &conversations[dir],
+                            &mut histories,
+                        )
+                        .await?;
+                    if count != posts.len() {
+                        problems.error(
+                            Category::Mismatch,
+                            &format!("{}:{}", name, entry),
+                            format!("{} messages, expected {}", posts.len(), count),
+                        );
+                    }
+                }
+
+                if self.config.skip_downloading {
+                    continue;
+                }
+                for file in posts
+                    .iter()
+                    .flat_map(|p| p.file.iter().chain(p.files.iter()))
+                    .filter(|f| !f.is_external && !f.url_for_download().is_empty())
+                {
+                    let upload = format!("__uploads/{}/{}", file.id, file.name);
+                    if !entry_names.contains(upload.as_str()) {
+                        problems.error(
+                            Category::Missing,
+                            &format!("{}:{}", name, entry),
+                            format!("attachment {} is missing", upload),
+                        );
+                    }
+                }
+            }
+        }
+
+        for (source, (source_idx, dir)) in expected.iter() {
+            let count = seen.get(source).copied().unwrap_or_default();
+            // Day files without new posts are not exported by incremental runs
+            if count == 0
+                && self.since.is_some()
+                && self
+                    .expected_posts(
+                        source,
+                        *source_idx,
+                        dir,
+                        &conversations[dir],
+                        &mut histories,
+                    )
+                    .await?
+                    == 0
+            {
+                continue;
+            }
+            match count {
+                0 => problems.error(
+                    Category::Missing,
+                    source,
+                    "day file is not exported".to_string(),
+                ),
+                1 => {}
+                n => problems.error(
+                    Category::Duplicate,
+                    source,
+                    format!("day file is exported {} times", n),
+                ),
+            }
+        }
+        for source in seen.keys().filter(|s| !expected.contains_key(*s)) {
+            problems.warning(
+                Category::Orphan,
+                source,
+                "day file is not expected in the output".to_string(),
+            );
+        }
+        Ok(problems)
+    }
+
+    // Number of posts transform keeps from the source day file, histories
+    // of conversations are cached by directory
+    async fn expected_posts(
+        &mut self,
+        source: &str,
+        idx: usize,
+        dir: &str,
+        files: &[(String, usize)],
+        histories: &mut HashMap<String, History>,
+    ) -> Result<usize> {
+        if !histories.contains_key(dir) {
+            let history = self.conversation_history(files).await?;
+            histories.insert(dir.to_string(), history);
+        }
+        let posts: Vec<SlackPost> =
+            serde_json::from_slice(&self.read_entry(idx).await?).unwrap_or_default();
+        Ok(apply_history(
+            posts,
+            &histories[dir],
+            self.config.preserve_edits,
+            self.config.deleted_messages,
+        )
+        .iter()
+        .filter(|p| {
+            self.since
+                .as_ref()
+                .map(|since| since.is_new_post(dir_of(source), p))
+                .unwrap_or(true)
+        })
+        .filter(|p| {
+            p.str_field("subtype")
+                .map(|s| self.config.subtype_policy.action(s) == SubtypeAction::Keep)
+                .unwrap_or(true)
+        })
+        .count())
+    }
+
+    // Check structural integrity and references of the export
+    pub async fn validate(&mut self) -> Result<Problems> {
+        self.scan_files().await?;
+        let mut problems = Problems::default();
+
+        let mut shared: Vec<(String, usize)> = self
+            .shared_files_idx
+            .iter()
+            .map(|(name, idx)| (name.clone(), *idx))
+            .collect();
+        shared.sort();
+
+        let mut user_ids: HashSet<String> = HashSet::new();
+        let mut has_users = false;
+        // Listed directory to its members
+        let mut listed: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (name, idx) in shared {
+            let buffer = self.read_entry(idx).await?;
+            let listing = split_workspace(&name).1;
+            let result = match listing {
+                "users.json" | "org_users.json" => {
+                    has_users = true;
+                    serde_json::from_slice::<Vec<User>>(&buffer).map(|users| {
+                        for user in users {
+                            if !user_ids.insert(user.id.clone()) && listing == "users.json" {
+                                problems.warning(
+                                    Category::Duplicate,
+                                    &name,
+                                    format!("user {} is listed twice", user.id),
+                                );
+                            }
+                        }
+                    })
+                }
+                "dms.json" => serde_json::from_slice::<Vec<Direct>>(&buffer).map(|dms| {
+                    for dm in dms {
+                        listed.insert(dm.id, dm.members);
+                    }
+                }),
+                _ if CHANNEL_NAMES.contains(&listing) => {
+                    let workspace = split_workspace(&name).0;
+                    serde_json::from_slice::<Vec<Channel>>(&buffer).map(|channels| {
+                        for channel in channels {
+                            let dir = workspace_path(workspace, &channel.name);
+                            if listed.insert(dir.clone(), channel.members).is_some() {
+                                problems.error(
+                                    Category::Duplicate,
+                                    &name,
+                                    format!("channel name {} is used twice", dir),
+                                );
+                            }
+                        }
+                    })
+                }
+                _ => serde_json::from_slice::<serde_json::Value>(&buffer).map(|_| ()),
+            };
+            if let Err(e) = result {
+                problems.error(Category::Json, &name, e.to_string());
+            }
+        }
+        if !has_users {
+            problems.error(
+                Category::Missing,
+                "users.json",
+                "users listing is not found".to_string(),
+            );
+        }
+
+        for (dir, members) in listed.iter() {
+            if !self.grouped_files_idx.contains_key(dir) {
+                problems.warning(
+                    Category::Missing,
+                    dir,
+                    "listed conversation has no directory".to_string(),
+                );
+            }
+            let unknown: Vec<&str> = members
+                .iter()
+                .filter(|m| has_users && !user_ids.contains(*m))
+                .map(|m| m.as_str())
+                .collect();
+            if !unknown.is_empty() {
+                problems.warning(
+                    Category::Reference,
+                    dir,
+                    format!("unknown members: {}", unknown.join(", ")),
+                );
+            }
+        }
+
+        let mut dirs: Vec<(String, Vec<(String, usize)>)> = self
+            .grouped_files_idx
+            .iter()
+            .map(|(dir, files)| (dir.clone(), files.clone()))
+            .collect();
+        dirs.sort();
+        for (dir, files) in dirs {
+            if !listed.contains_key(&dir) {
+                problems.error(
+                    Category::Orphan,
+                    &dir,
+                    "directory is not listed in channels, groups, mpims or dms".to_string(),
+                );
+            }
+            let mut unknown_authors: BTreeSet<String> = BTreeSet::new();
+            for (filename, idx) in files {
+                if !validate::is_day_file(&filename) {
+                    problems.warning(
+                        Category::Layout,
+                        &filename,
+                        "file name is not a date".to_string(),
+                    );
+                }
+                let buffer = self.read_entry(idx).await?;
+                match serde_json::from_slice::<Vec<SlackPost>>(&buffer) {
+                    Ok(posts) => unknown_authors.extend(
+                        posts
+                            .iter()
+                            .filter_map(|p| p.user())
+                            .filter(|u| has_users && *u != "USLACKBOT" && !user_ids.contains(*u))
+                            .map(|u| u.to_string()),
+                    ),
+                    Err(e) => problems.error(Category::Json, &filename, e.to_string()),
+                }
+            }
+            if !unknown_authors.is_empty() {
+                problems.warning(
+                    Category::Reference,
+                    &dir,
+                    format!(
+                        "posts by users missing from users.json: {}",
+                        unknown_authors.into_iter().collect::<Vec<_>>().join(", ")
+                    ),
+                );
+            }
+        }
+        Ok(problems)
+    }
+
+    // Channel directory to its kind, by shared listings
+    async fn channel_kinds(&mut self) -> Result<HashMap<String, ChannelKind>> {
+        let mut listings: Vec<(String, usize)> = self
+            .shared_files_idx
+            .iter()
+            .filter(|(name, _)| ChannelKind::from_listing(split_workspace(name).1).is_some())
+            .map(|(name, idx)| (name.clone(), *idx))
+            .collect();
+        listings.sort();
+
+        let mut kinds = HashMap::new();
+        for (name, idx) in listings {
+            let (workspace, listing) = split_workspace(&name);
+            let kind = ChannelKind::from_listing(listing).unwrap_or_default();
+            let buffer = self.read_entry(idx).await?;
+            let dirs: Vec<String> = match kind {
+                ChannelKind::Direct => serde_json::from_slice::<Vec<Direct>>(&buffer)
+                    .map(|dms| dms.into_iter().map(|d| d.id).collect()),
+                _ => serde_json::from_slice::<Vec<Channel>>(&buffer).map(|channels| {
+                    channels
+                        .into_iter()
+                        .map(|c| workspace_path(workspace, &c.name))
+                        .collect()
+                }),
+            }
+            .map_err(|e| anyhow!("Failed to deserialize {}: {}", name, e))?;
+            kinds.extend(dirs.into_iter().map(|dir| (dir, kind)));
+        }
+        Ok(kinds)
+    }
+
+    async fn channel_stats(
+        &mut self,
+        dir: &str,
+        kind: ChannelKind,
+        files: Vec<(String, usize)>,
+    ) -> Result<ChannelStats> {
+        let mut stats = ChannelStats {
+            name: dir.to_string(),
+            kind,
+            ..Default::default()
+        };
+        for (filename, idx) in files {
+            let size = self.reader.file().entries()[idx].uncompressed_size();
+            let buffer = self.read_entry(idx).await?;
+            let posts: Vec<SlackPost> = serde_json::from_slice(&buffer).unwrap_or_default();
+            stats.add_day(&filename, size, &posts);
+        }
+        Ok(stats)
+    }
+
+    fn print_summary(&self) {
+        info!("Summary:");
+        for (subtype, count) in self.subtype_counts.iter() {
+            info!(
+                "  {}: {} posts ({:?})",
+                subtype,
+                count,
+                self.config.subtype_policy.action(subtype)
+            );
+        }
+    }
+
+    pub async fn fetch_channels(&mut self) -> Result<()> {
+        let mut names: Vec<(String, usize)> = self
+            .shared_files_idx
+            .iter()
+            .filter(|(name, _)| CHANNEL_NAMES.contains(&split_workspace(name).1))
+            .map(|(name, idx)| (name.clone(), *idx))
+            .collect();
+        names.sort();
+        if names.is_empty() {
+            warn!("No channels found in shared files");
+        }
+
+        // Channel id to directory, shared channels are exported once
+        let mut seen: HashMap<String, String> = HashMap::new();
+        let deleted = self.skipped_users();
+        for (name, idx) in names {
+            let buffer = self.read_entry(idx).await?;
+            let mut channels: Vec<Channel> = match serde_json::from_slice(&buffer) {
+                Ok(channels) => channels,
+                Err(e) => {
+                    bail!("Failed to deserialize {}: {}", name, e);
+                }
+            };
+            info!(
+                "Successfully deserialized {} with {} entries",
+                name,
+                channels.len()
+            );
+
+            let workspace = split_workspace(&name).0.to_string();
+            // Group DMs are not limited for guests
+            let is_mpims = split_workspace(&name).1 == "mpims.json";
+            channels.retain(|channel| {
+                let dir = workspace_path(&workspace, &channel.name);
+                if channel.is_archived && self.config.skip_archived {
+                    info!("Skip archived channel {}", dir);
+                    self.grouped_files_idx.remove(&dir);
+                    return false;
+                }
+                if is_mpims && channel.members.iter().any(|m| deleted.contains(m)) {
+                    info!("Skip group DM {} of deleted users", dir);
+                    self.grouped_files_idx.remove(&dir);
+                    return false;
+                }
+                match seen.get(&channel.id) {
+                    Some(first) if *first != dir => {
+                        info!("Shared channel {} is exported from {}", dir, first);
+                        self.merge_shared_channel(first.clone(), &dir);
+                        false
+                    }
+                    _ => {
+                        seen.insert(channel.id.clone(), dir);
+                        true
+                    }
+                }
+            });
+
+            for channel in channels.iter_mut() {
+                if channel.truncate_texts() {
+                    warn!(
+                        "Topic or purpose of channel {} is truncated to Mattermost limits",
+                        channel.name
+                    );
+                }
+                let members = channel.members.len();
+                channel.members.retain(|m| !deleted.contains(m));
+                if channel.members.len() < members {
+                    info!(
+                        "Skip {} deleted members of channel {}",
+                        members - channel.members.len(),
+                        channel.name
+                    );
+                }
+                self.user_mapping.apply_members(&mut channel.members);
+                channel.creator = self.user_mapping.canonical_id(&channel.creator).to_string();
+                if is_mpims {
+                    continue;
+                }
+                for id in self
+                    .guests
+                    .check_members(&workspace, &channel.name, &mut channel.members)
+                {
+                    warn!(
+                        "Guest {} is not allowed in channel {}, membership is skipped",
+                        self.guests.guests[&id].username, channel.name
+                    );
+                }
+            }
+            let archived = channels.iter().filter(|c| c.is_archived).count();
+            if archived > 0 {
+                info!("{} has {} archived channels", name, archived);
+            }
+
+            for channel in channels.iter().filter(|c| !c.pins.is_empty()) {
+                self.pins.insert(
+                    workspace_path(&workspace, &channel.name),
+                    channel.pins.iter().map(|p| p.id.clone()).collect(),
+                );
+            }
+            self.channels.insert(name, channels);
         }
-        if !self.config.skip_channels {
-            self.split_files_to_chunks();
-            self.export_channels_chunks().await?;
+
+        for guest in self.guests.overflowed() {
+            warn!(
+                "Single-channel guest {} is a member of {} channels",
+                guest.username,
+                guest.channels.len()
+            );
         }
         Ok(())
     }
 
+    // Move day files of shared channel copy to the first seen directory
+    fn merge_shared_channel(&mut self, first: String, dir: &str) {
+        let Some(files) = self.grouped_files_idx.remove(dir) else {
+            return;
+        };
+        let target = self.grouped_files_idx.entry(first.clone()).or_default();
+        for (filename, idx) in files {
+            let day = filename.rsplit('/').next().unwrap_or_default();
+            if !target
+                .iter()
+                .any(|(f, _)| f.rsplit('/').next() == Some(day))
+            {
+                target.push((format!("{}/{}", first, day), idx));
+            }
+        }
+    }
+
+    // Deleted users whose conversations and memberships are skipped
+    fn skipped_users(&self) -> HashSet<String> {
+        if self.config.deleted_users != DeletedUsersPolicy::Skip {
+            return HashSet::new();
+        }
+        self.users
+            .iter()
+            .filter(|u| u.deleted)
+            .map(|u| u.id.clone())
+            .collect()
+    }
+
     pub async fn fetch_directs(&mut self) -> Result<()> {
         let dms_file_idx = match self.shared_files_idx.get("dms.json") {
             Some(idx) => *idx,
@@ -97,9 +1281,7 @@ impl Splitter {
             }
         };
 
-        let mut reader = self.reader.reader_with_entry(dms_file_idx).await?;
-        let mut buffer: Vec<u8> = Vec::new();
-        reader.read_to_end(&mut buffer).await?;
+        let buffer = self.read_entry(dms_file_idx).await?;
 
         self.directs = match serde_json::from_slice(&buffer) {
             Ok(dms) => dms,
@@ -113,6 +1295,42 @@ impl Splitter {
             self.directs.len()
         );
 
+        let deleted = self.skipped_users();
+        let mut skipped: Vec<Direct> = Vec::new();
+        for mut dm in std::mem::take(&mut self.directs) {
+            if dm.members.iter().any(|m| deleted.contains(m)) {
+                skipped.push(dm);
+                continue;
+            }
+            // Users merged into one account are left with a notes-to-self
+            // conversation of the surviving account
+            let distinct = dm.members.iter().collect::<HashSet<_>>().len();
+            self.user_mapping.apply_members(&mut dm.members);
+            if dm.members.len() < 2 && distinct > 1 {
+                warn!(
+                    "Direct {} of merged users is imported as notes to self of {}",
+                    dm.id,
+                    dm.members.first().map(String::as_str).unwrap_or_default()
+                );
+            }
+            self.directs.push(dm);
+        }
+        if !skipped.is_empty() {
+            info!("Skip {} directs of deleted users", skipped.len());
+        }
+        for dm in skipped.iter() {
+            self.grouped_files_idx.remove(&dm.id);
+        }
+
+        for dm in self.directs.iter_mut() {
+            if !dm.pins.is_empty() {
+                self.pins.insert(
+                    dm.id.clone(),
+                    dm.pins.iter().map(|p| p.id.clone()).collect(),
+                );
+            }
+        }
+
         for dm in self.directs.iter() {
             if let Some((dir, files)) = self.grouped_files_idx.get_key_value(&dm.id) {
                 self.direct_files_idx.insert(dir.clone(), files.clone());
@@ -131,6 +1349,192 @@ impl Splitter {
         Ok(())
     }
 
+    pub async fn fetch_users(&mut self) -> Result<()> {
+        // Enterprise Grid keeps users of all workspaces in org_users.json
+        let mut names: Vec<String> = ["users.json", "org_users.json"]
+            .iter()
+            .find(|name| self.shared_files_idx.contains_key(**name))
+            .map(|name| vec![name.to_string()])
+            .unwrap_or_else(|| {
+                self.shared_files_idx
+                    .keys()
+                    .filter(|name| split_workspace(name).1 == "users.json")
+                    .cloned()
+                    .collect()
+            });
+        names.sort();
+        if names.is_empty() {
+            bail!("users.json not found in shared files");
+        }
+
+        let mut seen: HashSet<String> = HashSet::new();
+        for name in names {
+            let idx = self.shared_files_idx[&name];
+            let buffer = self.read_entry(idx).await?;
+            let users: Vec<User> = match serde_json::from_slice(&buffer) {
+                Ok(users) => users,
+                Err(e) => {
+                    bail!("Failed to deserialize {}: {}", name, e);
+                }
+            };
+            self.users
+                .extend(users.into_iter().filter(|u| seen.insert(u.id.clone())));
+        }
+
+        info!(
+            "Successfully deserialized users.json with {} entries",
+            self.users.len()
+        );
+
+        if self.config.skip_avatars || self.config.skip_downloading {
+            return Ok(());
+        }
+
+        for user in self.users.iter_mut() {
+            let Some(avatar_url) = user.profile.avatar_url() else {
+                continue;
+            };
+            let ext = url_extension(&avatar_url).unwrap_or_else(|| String::from("png"));
+            let filename = format!("__uploads/avatars/{}.{}", user.id, ext);
+            match reqwest::Url::parse(&avatar_url) {
+                Ok(url) => {
+                    self.avatar_downloads.push(Download::new(&url, &filename));
+                    user.profile_image = Some(filename);
+                }
+                Err(e) => {
+                    error!("Parse avatar url {} error: {e}", &avatar_url)
+                }
+            }
+        }
+
+        info!("Found {} user avatars", self.avatar_downloads.len());
+
+        Ok(())
+    }
+
+    // Normalize usernames, mapping from previous run in the output directory is reused
+    pub async fn map_users(&mut self) -> Result<()> {
+        let path = self.config.output.join("user_mapping.csv");
+        // Commands without output directory don't reuse mapping
+        let previous = if !self.config.output.as_os_str().is_empty() && path.exists() {
+            info!("Reuse user mapping from {:?}", path);
+            UserMapping::read_csv(&path)?
+        } else {
+            Vec::new()
+        };
+
+        let overrides = match self.config.user_map.clone() {
+            Some(path) => {
+                let overrides = UserMapping::read_overrides(&path)?;
+                info!("Loaded {} external user mappings", overrides.len());
+                overrides
+            }
+            None => HashMap::new(),
+        };
+
+        self.user_mapping = UserMapping::build(&self.users, &previous, &overrides);
+        self.map_deleted_users();
+        self.user_mapping.apply(&mut self.users);
+
+        if self.config.guest_policy == GuestPolicy::Member {
+            for user in self.users.iter_mut() {
+                user.is_restricted = false;
+                user.is_ultra_restricted = false;
+            }
+        }
+        self.guests = Guests::new(&self.users, &self.config.guest_channels);
+        if !self.guests.guests.is_empty() {
+            info!("Found {} guest users", self.guests.guests.len());
+        }
+
+        let renamed = self
+            .user_mapping
+            .entries
+            .iter()
+            .filter(|e| e.old_name != e.new_name)
+            .count();
+        info!(
+            "Mapped {} users, {} renamed",
+            self.user_mapping.entries.len(),
+            renamed
+        );
+
+        self.fixed_names = previous
+            .into_iter()
+            .map(|e| (e.slack_id, e.new_name))
+            .chain(overrides.into_iter().filter(|(key, _)| !key.contains('@')))
+            .collect();
+
+        if let Some(bot_user) = self.config.bot_user.clone() {
+            let found = self.user_mapping.entries.iter().find(|e| {
+                e.slack_id == bot_user || e.old_name == bot_user || e.new_name == bot_user
+            });
+            match found {
+                Some(entry) => {
+                    info!("Bot messages are posted as {}", entry.new_name);
+                    self.bot_user = Some(entry.slack_id.clone());
+                }
+                None => bail!("Bot user {} not found in users.json", bot_user),
+            }
+        }
+
+        Ok(())
+    }
+
+    // Merge deleted users into the former employee account with `map` policy
+    fn map_deleted_users(&mut self) {
+        if self.config.deleted_users != DeletedUsersPolicy::Map {
+            return;
+        }
+        let deleted: Vec<String> = self
+            .users
+            .iter()
+            .filter(|u| u.deleted)
+            .map(|u| u.id.clone())
+            .collect();
+        if deleted.is_empty() {
+            return;
+        }
+
+        let name = users::normalize_username(&self.config.former_user, FORMER_USER_ID);
+        if name != self.config.former_user {
+            warn!(
+                "Former user {} is imported as {}",
+                self.config.former_user, name
+            );
+        }
+        let account = users::service_account(FORMER_USER_ID, &name, "Former employee", true);
+        if self.user_mapping.add(&account, Some(&name)) {
+            self.users.push(account);
+        }
+        for id in deleted.iter() {
+            self.user_mapping.merge(id, FORMER_USER_ID);
+        }
+        info!("Mapped {} deleted users to {}", deleted.len(), name);
+    }
+
+    pub async fn fetch_emoji(&mut self) -> Result<()> {
+        let buffer = if let Some(path) = self.config.emoji_file.clone() {
+            tokio::fs::read(path).await?
+        } else if let Some(idx) = self.shared_files_idx.get("emoji.json").copied() {
+            self.read_entry(idx).await?
+        } else {
+            info!("No custom emoji listing found");
+            return Ok(());
+        };
+
+        self.emoji = match emoji::parse_emoji_list(&buffer) {
+            Ok(emoji) => emoji,
+            Err(e) => {
+                bail!("Failed to deserialize emoji listing: {}", e);
+            }
+        };
+
+        info!("Successfully fetched {} custom emoji", self.emoji.len());
+
+        Ok(())
+    }
+
     pub async fn scan_files(&mut self) -> Result<()> {
         info!("Scan zip file structure and split to chunks...");
         dbg!(&self.config);
@@ -148,23 +1552,38 @@ impl Splitter {
         for (idx, entry) in all_entries.iter().enumerate() {
             self.pb.inc(1);
             if let Ok(filename) = entry.filename().clone().into_string() {
-                if filename.ends_with(".json") && !SHARED_NAMES.contains(&filename.as_ref()) {
-                    let dirname = filename.split('/').next().unwrap_or("-");
-
+                if !filename.ends_with(".json") {
+                    continue;
+                }
+                let (workspace, relative) = split_workspace(&filename);
+                // Day files live in channel directories, other files are shared
+                if relative.contains('/') {
+                    self.workspaces.insert(workspace.to_string());
                     let entry = self
                         .grouped_files_idx
-                        .entry(dirname.to_string())
+                        .entry(dir_of(&filename).to_string())
                         .or_default();
 
                     entry.push((filename, idx));
                     // self.files_idx.insert(filename, idx);
-                } else if filename.ends_with(".json") {
+                } else {
                     self.shared_files_idx.insert(filename, idx);
                 }
             }
         }
         self.pb.finish();
         info!("Scanned: {} files", all_entries.len());
+        if self.workspaces.iter().any(|w| !w.is_empty()) {
+            info!(
+                "Enterprise Grid export, workspaces: {}",
+                self.workspaces
+                    .iter()
+                    .filter(|w| !w.is_empty())
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
         info!(
             "Fetch {} groups and direct channels",
             self.grouped_files_idx.len()
@@ -174,16 +1593,36 @@ impl Splitter {
 
     // Split direct files to chunks by full channels
     fn split_directs_to_chunks(&mut self) {
-        let keys: Vec<String> = self
+        let mut keys: Vec<String> = self
             .direct_files_idx
             .keys()
             .map(|k| k.to_string())
             .collect();
+        keys.sort();
+        if keys.is_empty() {
+            return;
+        }
+        self.chunk_directs(keys);
+    }
+
+    // Conversations per chunk: split into `num_chunks`, at most `chunk_size`
+    // each when set
+    fn chunk_len(&self, keys: usize) -> usize {
+        let len = (keys as f64 / self.config.num_chunks as f64).ceil() as usize;
+        match self.config.chunk_size {
+            Some(max) => len.min(max).max(1),
+            None => len.max(1),
+        }
+    }
 
-        let chunk_size = (keys.len() as f64 / self.config.num_chunks as f64).ceil() as usize;
+    fn chunk_directs(&mut self, keys: Vec<String>) {
+        let chunk_size = self.chunk_len(keys.len());
 
         for chunked_keys in keys.chunks(chunk_size) {
-            let mut chunk = Chunk::default();
+            let mut chunk = Chunk {
+                team: self.config.teams.get("").map(String::from),
+                ..Default::default()
+            };
             for key in chunked_keys {
                 let mut chunk_item = ChunkItem {
                     id: key.clone(),
@@ -200,18 +1639,32 @@ impl Splitter {
         }
     }
 
-    // Split all files to chunks by full channels
+    // Split all files to chunks by full channels, each workspace separately
     fn split_files_to_chunks(&mut self) {
-        let keys: Vec<String> = self
-            .grouped_files_idx
-            .keys()
-            .map(|k| k.to_string())
-            .collect();
+        for workspace in self.workspaces.clone() {
+            let mut keys: Vec<String> = self
+                .grouped_files_idx
+                .keys()
+                .filter(|k| split_workspace(k).0 == workspace)
+                .map(|k| k.to_string())
+                .collect();
+            keys.sort();
+            if keys.is_empty() {
+                continue;
+            }
+            self.split_workspace_to_chunks(&workspace, keys);
+        }
+    }
 
-        let chunk_size = (keys.len() as f64 / self.config.num_chunks as f64).ceil() as usize;
+    fn split_workspace_to_chunks(&mut self, workspace: &str, keys: Vec<String>) {
+        let chunk_size = self.chunk_len(keys.len());
 
         for chunked_keys in keys.chunks(chunk_size) {
-            let mut chunk = Chunk::default();
+            let mut chunk = Chunk {
+                workspace: workspace.to_string(),
+                team: self.config.teams.get(workspace).map(String::from),
+                ..Default::default()
+            };
             for key in chunked_keys {
                 let mut chunk_item = ChunkItem {
                     id: key.clone(),
@@ -228,15 +1681,57 @@ impl Splitter {
         }
     }
 
+    // Export custom emoji as Mattermost bulk import archive
+    pub async fn export_emoji_chunk(&mut self) -> Result<()> {
+        if self.config.skip_downloading {
+            warn!("Skip custom emoji export, images cannot be downloaded!!!");
+            return Ok(());
+        }
+
+        let archive_name = self.archive_name("emoji.zip");
+
+        let name = format!("emoji_{}", archive_name);
+        let output = self.config.output.join(&name);
+        self.scripts.emoji = Some(name);
+        info!("Export {} custom emoji: {:?}", self.emoji.len(), output);
+
+        let mut lines = vec![serde_json::to_string(
+            &serde_json::json!({"type": "version", "version": 1}),
+        )?];
+        let mut downloads: Vec<Download> = Vec::new();
+        for e in self.emoji.iter() {
+            match reqwest::Url::parse(&e.url) {
+                Ok(url) => {
+                    downloads.push(Download::new(&url, format!("data/{}", e.image_path())));
+                    lines.push(serde_json::to_string(&e.to_import_line())?);
+                }
+                Err(err) => {
+                    error!("Parse emoji {} url {} error: {err}", &e.name, &e.url)
+                }
+            }
+        }
+
+        let mut out_file = File::create(output).await?;
+        let mut writer = ZipFileWriter::with_tokio(&mut out_file);
+
+        self.write_file(
+            &mut writer,
+            String::from("import.jsonl"),
+            lines.join("\n").as_bytes(),
+        )
+        .await?;
+        let artefacts_dir = self.config.output.join("data");
+        self.download_and_zip(&mut writer, &downloads, artefacts_dir)
+            .await?;
+
+        writer.close().await?;
+
+        Ok(())
+    }
+
     pub async fn export_directs_chunks(&mut self) -> Result<()> {
-        let archive_name = self
-            .config
-            .slack_archive
-            .file_name()
-            .unwrap_or(OsStr::new("directs.zip"))
-            .to_str()
-            .unwrap_or("directs.zip")
-            .to_string();
+        let archive_name = self.archive_name("directs.zip");
+        let names = plan::chunk_names(&self.chunked_directs_idx, "directs", &archive_name);
 
         for (idx, chunk) in self.chunked_directs_idx.clone().into_iter().enumerate() {
             info!(
@@ -245,10 +1740,12 @@ impl Splitter {
                 self.chunked_directs_idx.len(),
                 chunk.items.len(),
             );
-            let output = self
-                .config
-                .output
-                .join(format!("directs_{:03}_{}", idx, archive_name));
+            let output = self.config.output.join(&names[idx]);
+            self.scripts.directs.push(ImportArchive {
+                name: names[idx].clone(),
+                workspace: chunk.workspace.clone(),
+                team: chunk.team.clone(),
+            });
 
             info!("Output: {:?}", output);
 
@@ -274,26 +1771,28 @@ impl Splitter {
     }
 
     pub async fn export_channels_chunks(&mut self) -> Result<()> {
-        let archive_name = self
-            .config
-            .slack_archive
-            .file_name()
-            .unwrap_or(OsStr::new("channels.zip"))
-            .to_str()
-            .unwrap_or("channels.zip")
-            .to_string();
+        let archive_name = self.archive_name("channels.zip");
 
-        for (idx, chunk) in self.chunked_files_idx.clone().into_iter().enumerate() {
+        let names = plan::chunk_names(&self.chunked_files_idx, "channels", &archive_name);
+        for (n, (chunk, name)) in self
+            .chunked_files_idx
+            .clone()
+            .into_iter()
+            .zip(names)
+            .enumerate()
+        {
             info!(
                 "Export {} of {} channels chunks: {}",
-                idx + 1,
+                n + 1,
                 self.chunked_files_idx.len(),
                 chunk.items.len(),
             );
-            let output = self
-                .config
-                .output
-                .join(format!("channels_{:03}_{}", idx, archive_name));
+            let output = self.config.output.join(&name);
+            self.scripts.channels.push(ImportArchive {
+                name,
+                workspace: chunk.workspace.clone(),
+                team: chunk.team.clone(),
+            });
 
             info!("Output: {:?}", output);
 
@@ -316,7 +1815,10 @@ impl Splitter {
         // Create out file
         let mut out_file = File::create(path).await?;
         let mut writer = ZipFileWriter::with_tokio(&mut out_file);
-        let mut downloads: Vec<Download> = Vec::new();
+        // Avatars are bundled into the first chunk only, other chunks
+        // do not reference them
+        let with_avatars = !self.avatar_downloads.is_empty() && !self.config.skip_downloading;
+        let mut downloads: Vec<Download> = std::mem::take(&mut self.avatar_downloads);
 
         self.pb = ProgressBar::new(chunk.items.len() as u64);
         self.pb.set_style(
@@ -326,22 +1828,30 @@ impl Splitter {
               .unwrap(),
           );
 
-        // Copy all shared files
-        for (filename, idx) in self
-            .shared_files_idx
-            .clone()
-            .into_iter()
-            .filter(|f| shared_files.contains(&f.0.as_str()))
-        {
-            self.parse_and_copy_file(idx, filename, &mut writer, &mut downloads)
-                .await?;
-        }
-
         // Copy other files
-        for ci in chunk.items.into_iter() {
+        for ci in chunk.items.iter() {
             self.pb.inc(1);
-            for (filename, idx) in ci.files {
-                self.parse_and_copy_file(idx, filename, &mut writer, &mut downloads)
+            let history = self.conversation_history(&ci.files).await?;
+            if history.unresolved() > 0 {
+                warn!(
+                    "Dropped {} edits of messages missing from {}",
+                    history.unresolved(),
+                    ci.id
+                );
+            }
+            for (filename, idx) in ci.files.clone() {
+                self.parse_and_copy_file(idx, filename, &history, &mut writer, &mut downloads)
+                    .await?;
+            }
+        }
+
+        // Copy all shared files after posts, system posts may update channels
+        for filename in shared_files {
+            if let Some(data) = self
+                .shared_data(&chunk.workspace, filename, with_avatars)
+                .await?
+            {
+                self.write_file(&mut writer, filename.to_string(), &data)
                     .await?;
             }
         }
@@ -352,32 +1862,50 @@ impl Splitter {
 
         self.pb.finish();
 
+        let artefacts_dir = self.config.output.join("__uploads");
+        self.download_and_zip(&mut writer, &downloads, artefacts_dir)
+            .await?;
+
+        writer.close().await?;
+
+        info!("Done...");
+
+        Ok(())
+    }
+
+    async fn download_and_zip(
+        &mut self,
+        writer: &mut ZipFileWriter<Compat<&mut File>>,
+        downloads: &[Download],
+        artefacts_dir: PathBuf,
+    ) -> Result<()> {
         if self.config.skip_downloading {
             warn!("Skip downloading artefacts!!!");
-        } else {
-            info!("Start downloading {} files", downloads.len());
-            let downloader = DownloaderBuilder::new()
-                .concurrent_downloads(self.config.concurrent)
-                .directory(self.config.output.clone())
-                .build();
-            let _summaries = downloader.download(&downloads).await;
+            return Ok(());
+        }
 
-            info!("Downloaded complete!..");
+        self.download(downloads).await;
 
-            // ADD Downloaded files to archive
+        // ADD Downloaded files to archive
 
-            if !downloads.is_empty() {
-                info!("Write upload files to zip archive...");
-                self.zip_downloaded_files(&mut writer, self.config.output.join("__uploads"))
-                    .await?;
-            }
+        if !downloads.is_empty() {
+            info!("Write upload files to zip archive...");
+            self.zip_downloaded_files(writer, artefacts_dir).await?;
         }
 
-        writer.close().await?;
+        Ok(())
+    }
 
-        info!("Done...");
+    // Download files into the output directory
+    async fn download(&self, downloads: &[Download]) {
+        info!("Start downloading {} files", downloads.len());
+        let downloader = DownloaderBuilder::new()
+            .concurrent_downloads(self.config.concurrent)
+            .directory(self.config.output.clone())
+            .build();
+        let _summaries = downloader.download(downloads).await;
 
-        Ok(())
+        info!("Downloaded complete!..");
     }
 
     async fn zip_downloaded_files(
@@ -468,6 +1996,70 @@ impl Splitter {
         Ok(())
     }
 
+    // Shared file content, converted models are used when loaded.
+    // Workspace files are preferred over org-wide ones.
+    async fn shared_data(
+        &mut self,
+        workspace: &str,
+        filename: &str,
+        with_avatars: bool,
+    ) -> Result<Option<Vec<u8>>> {
+        if filename == "users.json" {
+            if with_avatars {
+                return Ok(Some(serde_json::to_vec(&self.users)?));
+            }
+            let users: Vec<User> = self
+                .users
+                .iter()
+                .cloned()
+                .map(|user| User {
+                    profile_image: None,
+                    ..user
+                })
+                .collect();
+            return Ok(Some(serde_json::to_vec(&users)?));
+        }
+        for path in [workspace_path(workspace, filename), filename.to_string()] {
+            if let Some(channels) = self.channels.get(&path) {
+                return Ok(Some(serde_json::to_vec(channels)?));
+            }
+            if let Some(idx) = self.shared_files_idx.get(&path).copied() {
+                return Ok(Some(self.read_entry(idx).await?));
+            }
+        }
+        Ok(None)
+    }
+
+    // Channel listing `shared_data` finds for the workspace
+    fn has_listing(&self, workspace: &str, filename: &str) -> bool {
+        [workspace_path(workspace, filename), filename.to_string()]
+            .iter()
+            .any(|path| {
+                self.channels.contains_key(path) || self.shared_files_idx.contains_key(path)
+            })
+    }
+
+    // Edits and deletions across all day files of a conversation
+    async fn conversation_history(&mut self, files: &[(String, usize)]) -> Result<History> {
+        let mut files = files.to_vec();
+        files.sort();
+        let mut history = History::default();
+        for (_, idx) in files {
+            let buffer = self.read_entry(idx).await?;
+            if let Ok(posts) = serde_json::from_slice::<Vec<SlackPost>>(&buffer) {
+                history.add_day(&posts);
+            }
+        }
+        Ok(history)
+    }
+
+    async fn read_entry(&mut self, idx: usize) -> Result<Vec<u8>> {
+        let mut reader = self.reader.reader_with_entry(idx).await?;
+        let mut buffer: Vec<u8> = Vec::new();
+        reader.read_to_end(&mut buffer).await?;
+        Ok(buffer)
+    }
+
     async fn write_file(
         &mut self,
         writer: &mut ZipFileWriter<Compat<&mut File>>,
@@ -486,22 +2078,56 @@ impl Splitter {
         &mut self,
         idx: usize,
         filename: String,
+        history: &History,
         writer: &mut ZipFileWriter<Compat<&mut File>>,
         downloads: &mut Vec<Download>,
     ) -> Result<()> {
-        let mut reader = self.reader.reader_with_entry(idx).await?;
-        let mut buffer: Vec<u8> = Vec::new();
-        reader.read_to_end(&mut buffer).await?;
+        let mut buffer = self.read_entry(idx).await?;
 
         // Parse here...
-        if let Ok(mut posts) = serde_json::from_slice::<Vec<model::SlackPost>>(&buffer) {
+        if let Ok(posts) = serde_json::from_slice::<Vec<model::SlackPost>>(&buffer) {
+            self.state.add_day(&filename, &posts);
+            let mut posts = apply_history(
+                posts,
+                history,
+                self.config.preserve_edits,
+                self.config.deleted_messages,
+            );
+            // Edits of earlier posts are collapsed first, so only posts
+            // missing from the previous run are left
+            if let Some(since) = &self.since {
+                posts.retain(|p| since.is_new_post(dir_of(&filename), p));
+                if posts.is_empty() {
+                    return Ok(());
+                }
+            }
+            let mut posts = self.apply_subtype_policy(posts);
+            let pins = self.pins.get(dir_of(&filename));
             for post in posts.iter_mut() {
+                let pinned = post
+                    .str_field("ts")
+                    .map(|ts| pins.map(|p| p.contains(ts)).unwrap_or_default())
+                    .unwrap_or_default();
+                if pinned || post.is_pinned_to() {
+                    post.set("is_pinned", true);
+                }
+            }
+            for post in posts.iter_mut() {
+                if bots::is_bot_message(post) {
+                    bots::convert_bot_message(post, self.bot_user.as_deref());
+                }
+                render::render_post(post, true);
+
                 // For legacy posts...swap file to files..
                 if let Some(file) = post.file.clone() {
                     post.files.push(file);
                     post.file = None;
                 }
 
+                fallbacks::apply_fallbacks(post);
+                // Rendered blocks and huddle summaries carry the original user ids
+                self.user_mapping.apply_post(post);
+
                 if !post.files.is_empty()
                     && post.files.iter().any(|f| !f.url_for_download().is_empty())
                 {
@@ -513,11 +2139,107 @@ impl Splitter {
             buffer = serde_json::to_vec(&posts)?;
         }
 
-        let builder = ZipEntryBuilder::new(ZipString::from(filename), Compression::Deflate);
+        let name = split_workspace(&filename).1;
+        let builder = ZipEntryBuilder::new(ZipString::from(name), Compression::Deflate);
         writer.write_entry_whole(builder, &buffer).await?;
         Ok(())
     }
 
+    // Find Slack Connect authors of exported conversations before any chunk
+    // is written, every chunk gets their placeholder users
+    async fn scan_external_users(&mut self, chunks: &[Chunk]) -> Result<()> {
+        for ci in chunks.iter().flat_map(|c| c.items.iter()) {
+            let mut files = ci.files.clone();
+            files.sort();
+            for (filename, idx) in files {
+                let buffer = self.read_entry(idx).await?;
+                let Ok(posts) = serde_json::from_slice::<Vec<SlackPost>>(&buffer) else {
+                    continue;
+                };
+                for post in posts.iter() {
+                    self.track_external(post, dir_of(&filename));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Register Slack Connect author missing from users.json, a placeholder user
+    // is added unless the author is mapped to an existing account
+    fn track_external(&mut self, post: &SlackPost, channel: &str) {
+        let Some(id) = post.user() else {
+            return;
+        };
+        if let Some(external) = self.external_users.get_mut(id) {
+            external.posts += 1;
+            external.channels.insert(channel.to_string());
+            return;
+        }
+        if id == "USLACKBOT" || self.user_mapping.get(id).is_some() || bots::is_bot_message(post) {
+            return;
+        }
+
+        let mut external = ExternalUser::from_post(id, post);
+        let mut placeholder = external.placeholder(post);
+        let fixed_name = self
+            .fixed_names
+            .get(id)
+            .cloned()
+            .or_else(|| self.external_account());
+        if self.user_mapping.add(&placeholder, fixed_name.as_deref()) {
+            placeholder.name = self.user_mapping.get(id).unwrap().new_name.clone();
+            self.users.push(placeholder);
+        }
+        external.mattermost_name = self.user_mapping.get(id).unwrap().new_name.clone();
+        external.posts = 1;
+        external.channels.insert(channel.to_string());
+        info!(
+            "External user {} ({}) is imported as {}",
+            id, external.real_name, external.mattermost_name
+        );
+        self.external_users.insert(id.to_string(), external);
+    }
+
+    // Name of the account for all external users, created on first use
+    fn external_account(&mut self) -> Option<String> {
+        let name = self.config.external_user.clone()?;
+        let account = external::external_account(&name);
+        let found = self.user_mapping.entries.iter().find(|e| {
+            e.slack_id == name
+                || e.old_name == name
+                || e.new_name == name
+                || e.new_name == account.name
+        });
+        if let Some(entry) = found {
+            return Some(entry.new_name.clone());
+        }
+        if account.name != name {
+            warn!("External user {} is imported as {}", name, account.name);
+        }
+        let name = account.name.clone();
+        self.user_mapping.add(&account, Some(&name));
+        self.users.push(account);
+        Some(name)
+    }
+
+    fn apply_subtype_policy(&mut self, posts: Vec<SlackPost>) -> Vec<SlackPost> {
+        posts
+            .into_iter()
+            .filter(|post| {
+                let Some(subtype) = post.str_field("subtype") else {
+                    return true;
+                };
+                *self.subtype_counts.entry(subtype.to_string()).or_default() += 1;
+                match self.config.subtype_policy.action(subtype) {
+                    SubtypeAction::Keep => true,
+                    SubtypeAction::Drop => false,
+                    // Channels are updated by `apply_channel_updates`
+                    SubtypeAction::Update => false,
+                }
+            })
+            .collect()
+    }
+
     async fn push_to_download(
         &mut self,
         files: &[model::File],
@@ -532,7 +2254,7 @@ impl Splitter {
             let url = reqwest::Url::parse(&file.url_for_download());
             match url {
                 Ok(url) => {
-                    downloads.push(Download { url, filename });
+                    downloads.push(Download::new(&url, &filename));
                 }
                 Err(e) => {
                     error!("Parse url {} error: {e}", &file.url_for_download())
@@ -0,0 +1,190 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use anyhow::{anyhow, bail, Error, Result};
+use serde::{Deserialize, Serialize};
+
+use super::model::{Channel, ChannelText, SlackPost};
+
+// Subtypes which can be converted to channel header/purpose
+static UPDATE_SUBTYPES: &[&str] = &[
+    "channel_topic",
+    "channel_purpose",
+    "group_topic",
+    "group_purpose",
+];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubtypeAction {
+    // Skip the post
+    Drop,
+    // Import as is, Mattermost shows it as a system post
+    #[default]
+    Keep,
+    // Update channel header/purpose and skip the post
+    Update,
+}
+
+impl FromStr for SubtypeAction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "drop" => Ok(SubtypeAction::Drop),
+            "keep" => Ok(SubtypeAction::Keep),
+            "update" => Ok(SubtypeAction::Update),
+            _ => bail!(
+                "Unknown subtype action {}, expected drop, keep or update",
+                s
+            ),
+        }
+    }
+}
+
+impl fmt::Display for SubtypeAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SubtypeAction::Drop => "drop",
+            SubtypeAction::Keep => "keep",
+            SubtypeAction::Update => "update",
+        })
+    }
+}
+
+// `subtype=action` rule from command line, config files use the same string
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SubtypeRule {
+    pub subtype: String,
+    pub action: SubtypeAction,
+}
+
+impl FromStr for SubtypeRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (subtype, action) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Subtype rule {} must look like subtype=action", s))?;
+        let action: SubtypeAction = action.trim().parse()?;
+        let subtype = subtype.trim().to_string();
+        if action == SubtypeAction::Update && !UPDATE_SUBTYPES.contains(&subtype.as_str()) {
+            bail!(
+                "Subtype {} cannot be converted to channel update, supported: {}",
+                subtype,
+                UPDATE_SUBTYPES.join(", ")
+            );
+        }
+        Ok(SubtypeRule { subtype, action })
+    }
+}
+
+impl TryFrom<String> for SubtypeRule {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<SubtypeRule> for String {
+    fn from(rule: SubtypeRule) -> Self {
+        format!("{}={}", rule.subtype, rule.action)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SubtypePolicy {
+    rules: HashMap<String, SubtypeAction>,
+}
+
+impl SubtypePolicy {
+    pub fn new(rules: &[SubtypeRule]) -> Self {
+        SubtypePolicy {
+            rules: rules
+                .iter()
+                .map(|r| (r.subtype.clone(), r.action))
+                .collect(),
+        }
+    }
+
+    pub fn has_updates(&self) -> bool {
+        self.rules.values().any(|a| *a == SubtypeAction::Update)
+    }
+
+    // Subtypes without a rule are kept
+    pub fn action(&self, subtype: &str) -> SubtypeAction {
+        self.rules.get(subtype).copied().unwrap_or_default()
+    }
+}
+
+// Apply topic/purpose change from system post to the channel
+pub fn apply_update(post: &SlackPost, channel: &mut Channel) {
+    let (field, target) = match post.str_field("subtype") {
+        Some("channel_topic") | Some("group_topic") => ("topic", &mut channel.topic),
+        Some("channel_purpose") | Some("group_purpose") => ("purpose", &mut channel.purpose),
+        _ => return,
+    };
+    let Some(value) = post.str_field(field) else {
+        return;
+    };
+    let last_set = post
+        .str_field("ts")
+        .and_then(|ts| ts.split('.').next())
+        .and_then(|ts| ts.parse().ok())
+        .unwrap_or_default();
+    let text = target.get_or_insert_with(ChannelText::default);
+    // Day files are processed in order, but keep the latest change anyway
+    if last_set >= text.last_set {
+        text.value = value.to_string();
+        text.creator = post.user().unwrap_or_default().to_string();
+        text.last_set = last_set;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_subtype_rule() {
+        let rule: SubtypeRule = "channel_join=drop".parse().unwrap();
+        assert_eq!(rule.subtype, "channel_join");
+        assert_eq!(rule.action, SubtypeAction::Drop);
+
+        assert!("channel_join".parse::<SubtypeRule>().is_err());
+        assert!("channel_join=update".parse::<SubtypeRule>().is_err());
+        assert!("channel_join=remove".parse::<SubtypeRule>().is_err());
+        assert_eq!(
+            serde_json::to_value(&rule).unwrap(),
+            json!("channel_join=drop")
+        );
+        assert!(serde_json::from_value::<SubtypeRule>(json!("channel_join=update")).is_err());
+
+        let policy = SubtypePolicy::new(&[rule]);
+        assert_eq!(policy.action("channel_join"), SubtypeAction::Drop);
+        assert_eq!(policy.action("channel_leave"), SubtypeAction::Keep);
+        assert!(!policy.has_updates());
+    }
+
+    #[test]
+    fn test_apply_update() {
+        let mut channel = Channel::default();
+        let post: SlackPost = serde_json::from_value(json!({
+            "subtype": "channel_topic",
+            "user": "U1",
+            "topic": "Release planning",
+            "ts": "1600000007.000100"
+        }))
+        .unwrap();
+
+        apply_update(&post, &mut channel);
+
+        let topic = channel.topic.unwrap();
+        assert_eq!(topic.value, "Release planning");
+        assert_eq!(topic.creator, "U1");
+        assert_eq!(topic.last_set, 1600000007);
+        assert!(channel.purpose.is_none());
+    }
+}
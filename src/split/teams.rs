@@ -0,0 +1,106 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use anyhow::{bail, Error, Result};
+use serde::{Deserialize, Serialize};
+
+// `workspace=team` rule from command line, a plain team name is the default
+// for workspaces without a rule and for regular exports. Config files use
+// the same string
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TeamRule {
+    pub workspace: Option<String>,
+    pub team: String,
+}
+
+impl FromStr for TeamRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (workspace, team) = match s.split_once('=') {
+            Some((workspace, team)) => (Some(workspace.trim().to_string()), team.trim()),
+            None => (None, s.trim()),
+        };
+        if team.is_empty() || workspace.as_deref() == Some("") {
+            bail!("Team {} must look like team or workspace=team", s);
+        }
+        Ok(TeamRule {
+            workspace,
+            team: team.to_string(),
+        })
+    }
+}
+
+impl TryFrom<String> for TeamRule {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<TeamRule> for String {
+    fn from(rule: TeamRule) -> Self {
+        match rule.workspace {
+            Some(workspace) => format!("{}={}", workspace, rule.team),
+            None => rule.team,
+        }
+    }
+}
+
+// Mattermost team each workspace is imported into
+#[derive(Debug, Default, Clone)]
+pub struct Teams {
+    default: Option<String>,
+    workspaces: BTreeMap<String, String>,
+}
+
+impl Teams {
+    pub fn new(rules: &[TeamRule]) -> Self {
+        let mut teams = Teams::default();
+        for rule in rules {
+            match &rule.workspace {
+                Some(workspace) => {
+                    teams
+                        .workspaces
+                        .insert(workspace.clone(), rule.team.clone());
+                }
+                None => teams.default = Some(rule.team.clone()),
+            }
+        }
+        teams
+    }
+
+    // Team of the workspace, empty workspace is a regular export
+    pub fn get(&self, workspace: &str) -> Option<&str> {
+        self.workspaces
+            .get(workspace)
+            .or(self.default.as_ref())
+            .map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_team_rules() {
+        let rules: Vec<TeamRule> = ["acme", "sales=acme-sales"]
+            .iter()
+            .map(|r| r.parse().unwrap())
+            .collect();
+        assert_eq!(rules[0].workspace, None);
+        assert_eq!(rules[1].workspace.as_deref(), Some("sales"));
+        assert!("sales=".parse::<TeamRule>().is_err());
+        assert!("=acme".parse::<TeamRule>().is_err());
+        let strings: Vec<String> = rules.iter().cloned().map(String::from).collect();
+        assert_eq!(strings, vec!["acme", "sales=acme-sales"]);
+
+        let teams = Teams::new(&rules);
+        assert_eq!(teams.get("sales"), Some("acme-sales"));
+        assert_eq!(teams.get("support"), Some("acme"));
+        assert_eq!(teams.get(""), Some("acme"));
+        assert_eq!(Teams::default().get(""), None);
+    }
+}
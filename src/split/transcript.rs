@@ -0,0 +1,378 @@
+use std::{collections::HashMap, fmt::Write};
+
+use chrono::TimeZone;
+use chrono_tz::Tz;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::{
+    merge::ts_key,
+    model::{ts_to_millis, SlackPost},
+};
+
+lazy_static! {
+    // `<@U1|name>`, `<#C1|name>`, `<!here>` and `<https://url|label>`
+    static ref MARKUP_RE: Regex = Regex::new(r"<([@#!]?)([^<>|]*)(?:\|([^<>]*))?>").unwrap();
+}
+
+// Readable conversation history for legal holds, threads are nested
+// under their parent messages
+#[derive(Debug, Default)]
+pub struct Transcript {
+    pub title: String,
+    pub messages: Vec<Message>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Message {
+    pub ts: String,
+    pub author: String,
+    pub time: String,
+    // Time of the last edit, kept with `--preserve-edits`
+    pub edited: Option<String>,
+    pub text: String,
+    pub html: String,
+    pub files: Vec<Attachment>,
+    pub replies: Vec<Message>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Attachment {
+    pub name: String,
+    pub link: String,
+}
+
+impl Transcript {
+    // `names` are usernames by Slack id. Attachments link to `uploads`
+    // copies when set, to Slack otherwise.
+    pub fn new(
+        title: String,
+        mut posts: Vec<SlackPost>,
+        names: &HashMap<String, String>,
+        timezone: Tz,
+        uploads: Option<&str>,
+    ) -> Self {
+        posts.sort_by_key(|p| ts_key(p.str_field("ts").unwrap_or_default()));
+        let mut messages: Vec<Message> = Vec::new();
+        let mut threads: HashMap<String, usize> = HashMap::new();
+        for post in posts.iter() {
+            let message = Message::new(post, names, timezone, uploads);
+            let parent = post
+                .str_field("thread_ts")
+                .filter(|thread_ts| *thread_ts != message.ts)
+                .and_then(|thread_ts| threads.get(thread_ts));
+            match parent {
+                Some(idx) => messages[*idx].replies.push(message),
+                None => {
+                    threads.insert(message.ts.clone(), messages.len());
+                    messages.push(message);
+                }
+            }
+        }
+        Transcript { title, messages }
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>",
+            escape_html(&self.title)
+        );
+        out.push_str(HTML_STYLE);
+        let _ = writeln!(
+            out,
+            "</head>\n<body>\n<h1>{}</h1>",
+            escape_html(&self.title)
+        );
+        for message in self.messages.iter() {
+            message.write_html(&mut out);
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = format!("{}\n\n", self.title);
+        for message in self.messages.iter() {
+            message.write_text(&mut out, "");
+        }
+        out
+    }
+}
+
+impl Message {
+    fn new(
+        post: &SlackPost,
+        names: &HashMap<String, String>,
+        timezone: Tz,
+        uploads: Option<&str>,
+    ) -> Self {
+        let ts = post.str_field("ts").unwrap_or_default().to_string();
+        let bot_name = post
+            .extra
+            .get("props")
+            .and_then(|props| props.get("override_username"))
+            .and_then(|name| name.as_str());
+        let author = bot_name
+            .map(String::from)
+            .or_else(|| post.user().and_then(|id| names.get(id).cloned()))
+            .or_else(|| post.str_field("username").map(String::from))
+            .or_else(|| post.user().map(String::from))
+            .unwrap_or_else(|| String::from("unknown"));
+        let format_time = |millis: i64| {
+            timezone
+                .timestamp_millis_opt(millis)
+                .single()
+                .map(|time| time.format("%Y-%m-%d %H:%M:%S %Z").to_string())
+        };
+        let time = ts_to_millis(&ts).and_then(format_time).unwrap_or_default();
+        let edited = post
+            .extra
+            .get("edit_at")
+            .and_then(|edit_at| edit_at.as_i64())
+            .and_then(format_time);
+        let text = post.text().unwrap_or_default();
+        let files = post
+            .file
+            .iter()
+            .chain(post.files.iter())
+            .map(|file| Attachment {
+                name: file.name.clone(),
+                link: match uploads {
+                    Some(uploads) if !file.is_external => format!(
+                        "{}/{}/{}",
+                        uploads,
+                        encode_segment(&file.id),
+                        encode_segment(&file.name)
+                    ),
+                    _ => file.url_for_download(),
+                },
+            })
+            .collect();
+        Message {
+            ts,
+            author,
+            time,
+            edited,
+            text: convert_markup(text, names, false),
+            html: convert_markup(text, names, true),
+            files,
+            replies: Vec::new(),
+        }
+    }
+
+    fn write_html(&self, out: &mut String) {
+        let edited = self
+            .edited
+            .as_ref()
+            .map(|time| {
+                format!(
+                    " <span class=\"edited\">(edited {})</span>",
+                    escape_html(time)
+                )
+            })
+            .unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "<div class=\"message\" id=\"{}\">\n<div class=\"meta\"><span class=\"author\">{}</span> <span class=\"time\">{}</span>{}</div>\n<div class=\"text\">{}</div>",
+            escape_html(&self.ts),
+            escape_html(&self.author),
+            escape_html(&self.time),
+            edited,
+            self.html
+        );
+        for file in self.files.iter() {
+            let _ = writeln!(
+                out,
+                "<div class=\"file\"><a href=\"{}\">{}</a></div>",
+                escape_html(&file.link),
+                escape_html(&file.name)
+            );
+        }
+        if !self.replies.is_empty() {
+            out.push_str("<div class=\"thread\">\n");
+            for reply in self.replies.iter() {
+                reply.write_html(out);
+            }
+            out.push_str("</div>\n");
+        }
+        out.push_str("</div>\n");
+    }
+
+    fn write_text(&self, out: &mut String, indent: &str) {
+        match &self.edited {
+            Some(edited) => {
+                let _ = writeln!(
+                    out,
+                    "{}[{}] {} (edited {}):",
+                    indent, self.time, self.author, edited
+                );
+            }
+            None => {
+                let _ = writeln!(out, "{}[{}] {}:", indent, self.time, self.author);
+            }
+        }
+        for line in self.text.lines() {
+            let _ = writeln!(out, "{}    {}", indent, line);
+        }
+        for file in self.files.iter() {
+            let _ = writeln!(
+                out,
+                "{}    Attachment: {} ({})",
+                indent, file.name, file.link
+            );
+        }
+        let nested = format!("{}    | ", indent);
+        for reply in self.replies.iter() {
+            reply.write_text(out, &nested);
+        }
+    }
+}
+
+const HTML_STYLE: &str = "<style>
+body { font-family: sans-serif; margin: 2em; }
+.message { margin: 0.8em 0; }
+.meta { color: #616061; font-size: 0.9em; }
+.edited { font-style: italic; }
+.author { font-weight: bold; color: #1d1c1d; }
+.text { white-space: pre-wrap; }
+.thread { margin-left: 2em; padding-left: 1em; border-left: 3px solid #ddd; }
+</style>
+";
+
+// Percent-encode path segment of a relative link, file names may contain
+// `#`, `?` or `%`
+fn encode_segment(segment: &str) -> String {
+    let mut out = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => {
+                let _ = write!(out, "%{:02X}", byte);
+            }
+        }
+    }
+    out
+}
+
+// Slack markup of export text as plain text or HTML. Export text is entity
+// encoded, so it is decoded before escaping once
+fn convert_markup(text: &str, names: &HashMap<String, String>, html: bool) -> String {
+    let escape = |s: &str| match html {
+        true => escape_html(s),
+        false => s.to_string(),
+    };
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for caps in MARKUP_RE.captures_iter(text) {
+        let (start, end) = caps
+            .get(0)
+            .map(|m| (m.start(), m.end()))
+            .unwrap_or_default();
+        out.push_str(&escape(&decode_entities(&text[last..start])));
+        last = end;
+        let target = decode_entities(&caps[2]);
+        let label = caps.get(3).map(|l| decode_entities(l.as_str()));
+        let plain = match &caps[1] {
+            "@" => format!(
+                "@{}",
+                names.get(&target).cloned().or(label).unwrap_or(target)
+            ),
+            "#" => format!("#{}", label.unwrap_or(target)),
+            "!" => match target.as_str() {
+                "here" | "channel" | "everyone" => format!("@{}", target),
+                // User groups and dates carry their own label
+                _ => label.unwrap_or(target),
+            },
+            _ if html => {
+                let _ = write!(
+                    out,
+                    "<a href=\"{}\">{}</a>",
+                    escape_html(&target),
+                    escape_html(label.as_deref().unwrap_or(&target))
+                );
+                continue;
+            }
+            _ => match label.filter(|l| *l != target) {
+                Some(label) => format!("{} ({})", label, target),
+                None => target,
+            },
+        };
+        out.push_str(&escape(&plain));
+    }
+    out.push_str(&escape(&decode_entities(&text[last..])));
+    out
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_transcript_threads() {
+        let posts: Vec<SlackPost> = serde_json::from_value(json!([
+            {"type": "message", "user": "U2", "text": "reply to <@U1>",
+             "ts": "1600000060.000100", "thread_ts": "1600000000.000100",
+             "edit_at": 1600000090000i64},
+            {"type": "message", "user": "U1",
+             "text": "hello &lt;b&gt; &amp; <https://x.io/?a=1&amp;b=2|docs> in <#C1|general> <!here> <https://y.io>",
+             "ts": "1600000000.000100", "thread_ts": "1600000000.000100",
+             "files": [{"id": "F1", "name": "a.png"}, {"id": "F2", "name": "50% #1?.png"}]},
+            {"type": "message", "user": "B1", "text": "done",
+             "ts": "1600000120.000100", "props": {"override_username": "deploy-bot"}}
+        ]))
+        .unwrap();
+        let names = HashMap::from([
+            ("U1".to_string(), "alice".to_string()),
+            ("U2".to_string(), "bob".to_string()),
+        ]);
+
+        let transcript = Transcript::new(
+            "#general".to_string(),
+            posts,
+            &names,
+            chrono_tz::Europe::Berlin,
+            Some("../__uploads"),
+        );
+
+        assert_eq!(transcript.messages.len(), 2);
+        let first = &transcript.messages[0];
+        assert_eq!(first.time, "2020-09-13 14:26:40 CEST");
+        assert_eq!(first.files[0].link, "../__uploads/F1/a.png");
+        assert_eq!(first.files[1].link, "../__uploads/F2/50%25%20%231%3F.png");
+        assert_eq!(
+            first.text,
+            "hello <b> & docs (https://x.io/?a=1&b=2) in #general @here https://y.io"
+        );
+        assert_eq!(first.replies[0].text, "reply to @alice");
+        assert_eq!(transcript.messages[1].author, "deploy-bot");
+
+        let html = transcript.to_html();
+        assert!(html.contains(
+            "<div class=\"text\">hello &lt;b&gt; &amp; <a href=\"https://x.io/?a=1&amp;b=2\">docs</a> in #general @here <a href=\"https://y.io\">https://y.io</a></div>"
+        ));
+        assert!(html.contains("<div class=\"thread\">"));
+        assert!(html.contains("<span class=\"edited\">(edited 2020-09-13 14:28:10 CEST)</span>"));
+        let text = transcript.to_text();
+        assert!(text.contains(
+            "    | [2020-09-13 14:27:40 CEST] bob (edited 2020-09-13 14:28:10 CEST):\n    |     reply to @alice\n"
+        ));
+    }
+}
@@ -0,0 +1,492 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{bail, Error, Result};
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::model::{SlackPost, User};
+
+const MIN_USERNAME_LEN: usize = 3;
+const MAX_USERNAME_LEN: usize = 22;
+
+static RESERVED_USERNAMES: &[&str] = &["all", "channel", "here", "matterbot", "system"];
+
+// Slack id of the account created for `--deleted-users map`
+pub const FORMER_USER_ID: &str = "UFORMER";
+
+lazy_static! {
+    static ref MENTION_RE: Regex = Regex::new(r"<@([UW][A-Z0-9]+)(\|[^>]*)?>").unwrap();
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeletedUsersPolicy {
+    // Import as deactivated accounts
+    #[default]
+    Import,
+    // Merge into a single deactivated "former employee" account
+    Map,
+    // Import as deactivated accounts, but skip their direct messages, group
+    // messages and channel memberships
+    Skip,
+}
+
+impl FromStr for DeletedUsersPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "import" => Ok(DeletedUsersPolicy::Import),
+            "map" => Ok(DeletedUsersPolicy::Map),
+            "skip" => Ok(DeletedUsersPolicy::Skip),
+            _ => bail!(
+                "Unknown deleted users policy {}, expected import, map or skip",
+                s
+            ),
+        }
+    }
+}
+
+// User record for accounts created by the splitter
+pub fn service_account(id: &str, name: &str, real_name: &str, deleted: bool) -> User {
+    serde_json::from_value(json!({
+        "id": id,
+        "name": name,
+        "real_name": real_name,
+        "deleted": deleted,
+        "profile": {"real_name": real_name}
+    }))
+    .unwrap_or_default()
+}
+
+// Row of external mapping file: Slack user id or email to Mattermost username
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ExternalMappingEntry {
+    pub slack: String,
+    pub mattermost: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UserMappingEntry {
+    pub slack_id: String,
+    pub old_name: String,
+    pub new_name: String,
+    pub email: String,
+}
+
+// Slack user id to Mattermost username mapping, shared by all chunks
+#[derive(Debug, Default, Clone)]
+pub struct UserMapping {
+    pub entries: Vec<UserMappingEntry>,
+    by_id: HashMap<String, usize>,
+    // Slack users mapped to the same Mattermost account, id to canonical id
+    merged: HashMap<String, String>,
+}
+
+impl UserMapping {
+    // Build mapping for users. External `overrides` (keyed by Slack id or
+    // lowercase email) win over names from `previous` mapping, which are kept as is.
+    // Active users claim names first, then users are ordered by id,
+    // so collisions are resolved the same way on every run.
+    pub fn build(
+        users: &[User],
+        previous: &[UserMappingEntry],
+        overrides: &HashMap<String, String>,
+    ) -> Self {
+        let previous: HashMap<&str, &UserMappingEntry> =
+            previous.iter().map(|e| (e.slack_id.as_str(), e)).collect();
+
+        let mut ordered: Vec<&User> = users.iter().collect();
+        ordered.sort_by(|a, b| (a.deleted, &a.id).cmp(&(b.deleted, &b.id)));
+
+        let fixed_name = |user: &User| -> Option<String> {
+            let email = user
+                .profile
+                .email
+                .clone()
+                .unwrap_or_default()
+                .to_lowercase();
+            overrides
+                .get(&user.id)
+                .or_else(|| overrides.get(&email).filter(|_| !email.is_empty()))
+                .cloned()
+                .or_else(|| previous.get(user.id.as_str()).map(|e| e.new_name.clone()))
+        };
+
+        let mut taken: HashSet<String> = users.iter().filter_map(fixed_name).collect();
+
+        let mut mapping = UserMapping::default();
+        let mut canonical: HashMap<String, String> = HashMap::new();
+        for user in ordered {
+            let new_name = match fixed_name(user) {
+                Some(name) => {
+                    // Several Slack users may share one existing account
+                    match canonical.get(&name) {
+                        Some(id) => {
+                            mapping.merged.insert(user.id.clone(), id.clone());
+                        }
+                        None => {
+                            canonical.insert(name.clone(), user.id.clone());
+                        }
+                    }
+                    name
+                }
+                None => {
+                    let base = normalize_username(&user.name, &user.id);
+                    let name = resolve_collision(&base, &taken);
+                    taken.insert(name.clone());
+                    name
+                }
+            };
+            mapping.push(UserMappingEntry {
+                slack_id: user.id.clone(),
+                old_name: user.name.clone(),
+                new_name,
+                email: user.profile.email.clone().unwrap_or_default(),
+            });
+        }
+        mapping
+    }
+
+    pub fn push(&mut self, entry: UserMappingEntry) {
+        self.by_id
+            .insert(entry.slack_id.clone(), self.entries.len());
+        self.entries.push(entry);
+    }
+
+    pub fn get(&self, slack_id: &str) -> Option<&UserMappingEntry> {
+        self.by_id.get(slack_id).map(|idx| &self.entries[*idx])
+    }
+
+    // Id of the user that represents merged Mattermost account
+    pub fn canonical_id<'a>(&'a self, slack_id: &'a str) -> &'a str {
+        self.merged
+            .get(slack_id)
+            .map(|id| id.as_str())
+            .unwrap_or(slack_id)
+    }
+
+    // Add user found after the mapping was built. Fixed name of an already
+    // mapped account merges the user into it, otherwise the name is normalized.
+    // Returns false for merged users, which need no user record.
+    pub fn add(&mut self, user: &User, fixed_name: Option<&str>) -> bool {
+        let existing = fixed_name.and_then(|name| {
+            self.entries
+                .iter()
+                .find(|e| e.new_name == name)
+                .map(|e| self.canonical_id(&e.slack_id).to_string())
+        });
+        let new_name = match fixed_name {
+            Some(name) => name.to_string(),
+            None => {
+                let taken: HashSet<String> =
+                    self.entries.iter().map(|e| e.new_name.clone()).collect();
+                resolve_collision(&normalize_username(&user.name, &user.id), &taken)
+            }
+        };
+        self.push(UserMappingEntry {
+            slack_id: user.id.clone(),
+            old_name: user.name.clone(),
+            new_name,
+            email: user.profile.email.clone().unwrap_or_default(),
+        });
+        match existing {
+            Some(id) => {
+                self.merged.insert(user.id.clone(), id);
+                false
+            }
+            None => true,
+        }
+    }
+
+    // Merge user into account of `canonical_id`
+    pub fn merge(&mut self, slack_id: &str, canonical_id: &str) {
+        let canonical_id = self.canonical_id(canonical_id).to_string();
+        let Some(new_name) = self.get(&canonical_id).map(|e| e.new_name.clone()) else {
+            return;
+        };
+        if let Some(idx) = self.by_id.get(slack_id) {
+            self.entries[*idx].new_name = new_name;
+        }
+        if slack_id != canonical_id {
+            self.merged.insert(slack_id.to_string(), canonical_id);
+        }
+    }
+
+    // Rename users to their Mattermost usernames and drop merged duplicates
+    pub fn apply(&self, users: &mut Vec<User>) {
+        users.retain(|u| !self.merged.contains_key(&u.id));
+        for user in users.iter_mut() {
+            if let Some(entry) = self.get(&user.id) {
+                user.name = entry.new_name.clone();
+            }
+        }
+    }
+
+    pub fn apply_members(&self, members: &mut Vec<String>) {
+        let mut seen = HashSet::new();
+        members.retain_mut(|m| {
+            *m = self.canonical_id(m).to_string();
+            seen.insert(m.clone())
+        });
+    }
+
+    // Rewrite post author and mentions of merged users
+    pub fn apply_post(&self, post: &mut SlackPost) {
+        if self.merged.is_empty() {
+            return;
+        }
+        if let Some(user) = post.user() {
+            let user = self.canonical_id(user).to_string();
+            post.set("user", user);
+        }
+        if let Some(text) = post.text() {
+            let text = MENTION_RE
+                .replace_all(text, |caps: &Captures| {
+                    format!(
+                        "<@{}{}>",
+                        self.canonical_id(&caps[1]),
+                        caps.get(2).map(|m| m.as_str()).unwrap_or_default()
+                    )
+                })
+                .into_owned();
+            post.set("text", text);
+        }
+    }
+
+    // Read external mapping from CSV (`slack,mattermost` header) or JSON
+    // (object or list of objects) file
+    pub fn read_overrides(path: &Path) -> Result<HashMap<String, String>> {
+        let entries: Vec<ExternalMappingEntry> = match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => csv::Reader::from_path(path)?
+                .deserialize()
+                .collect::<Result<Vec<_>, _>>()?,
+            Some("json") => {
+                let value: serde_json::Value = serde_json::from_slice(&std::fs::read(path)?)?;
+                match value {
+                    serde_json::Value::Object(_) => {
+                        serde_json::from_value::<HashMap<String, String>>(value)?
+                            .into_iter()
+                            .map(|(slack, mattermost)| ExternalMappingEntry { slack, mattermost })
+                            .collect()
+                    }
+                    _ => serde_json::from_value(value)?,
+                }
+            }
+            _ => bail!(
+                "Unsupported user mapping file {:?}, expected .csv or .json",
+                path
+            ),
+        };
+
+        Ok(entries
+            .into_iter()
+            .map(|e| {
+                let key = match e.slack.contains('@') {
+                    true => e.slack.trim().to_lowercase(),
+                    false => e.slack.trim().to_string(),
+                };
+                (key, e.mattermost.trim().to_string())
+            })
+            .collect())
+    }
+
+    pub fn read_csv(path: &Path) -> Result<Vec<UserMappingEntry>> {
+        let mut reader = csv::Reader::from_path(path)?;
+        let entries = reader.deserialize().collect::<Result<Vec<_>, _>>()?;
+        Ok(entries)
+    }
+
+    pub fn to_csv(&self) -> Result<Vec<u8>> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for entry in self.entries.iter() {
+            writer.serialize(entry)?;
+        }
+        Ok(writer.into_inner()?)
+    }
+}
+
+// Normalize name against Mattermost username rules:
+// 3-22 chars of lowercase letters, digits, '.', '-' and '_', starting with a letter
+pub fn normalize_username(name: &str, id: &str) -> String {
+    let mut username: String = name
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            'a'..='z' | '0'..='9' | '.' | '-' | '_' => Some(c),
+            c if c.is_whitespace() => Some('_'),
+            _ => None,
+        })
+        .skip_while(|c| !c.is_ascii_lowercase())
+        .take(MAX_USERNAME_LEN)
+        .collect();
+
+    if username.is_empty() {
+        username = format!("user_{}", id.to_lowercase());
+        username.truncate(MAX_USERNAME_LEN);
+    }
+    while username.len() < MIN_USERNAME_LEN {
+        username.push('_');
+    }
+    if RESERVED_USERNAMES.contains(&username.as_str()) {
+        username.push('_');
+    }
+    username
+}
+
+fn resolve_collision(base: &str, taken: &HashSet<String>) -> String {
+    if !taken.contains(base) {
+        return base.to_string();
+    }
+    (2..)
+        .map(|n| {
+            let suffix = n.to_string();
+            let mut name = base.to_string();
+            name.truncate(MAX_USERNAME_LEN - suffix.len());
+            name + &suffix
+        })
+        .find(|name| !taken.contains(name))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split::{fallbacks, render};
+    use serde_json::json;
+
+    fn user(id: &str, name: &str, deleted: bool) -> User {
+        serde_json::from_value(json!({"id": id, "name": name, "deleted": deleted})).unwrap()
+    }
+
+    #[test]
+    fn test_normalize_username() {
+        assert_eq!(normalize_username("John.Smith", "U1"), "john.smith");
+        assert_eq!(normalize_username("_john smith!", "U1"), "john_smith");
+        assert_eq!(normalize_username("jo", "U1"), "jo_");
+        assert_eq!(normalize_username("42", "U1"), "user_u1");
+        assert_eq!(normalize_username("here", "U1"), "here_");
+        assert_eq!(
+            normalize_username("a-very-long-slack-username", "U1"),
+            "a-very-long-slack-user"
+        );
+    }
+
+    #[test]
+    fn test_mapping_resolves_collisions() {
+        let users = vec![
+            user("U3", "Alice", false),
+            user("U1", "alice", true),
+            user("U2", "ALICE", false),
+        ];
+
+        let mapping = UserMapping::build(&users, &[], &HashMap::new());
+
+        assert_eq!(mapping.get("U2").unwrap().new_name, "alice");
+        assert_eq!(mapping.get("U3").unwrap().new_name, "alice2");
+        assert_eq!(mapping.get("U1").unwrap().new_name, "alice3");
+    }
+
+    #[test]
+    fn test_mapping_keeps_previous_names() {
+        let users = vec![user("U1", "alice", false), user("U2", "Alice", false)];
+        let previous = vec![UserMappingEntry {
+            slack_id: "U2".to_string(),
+            old_name: "Alice".to_string(),
+            new_name: "alice".to_string(),
+            email: String::new(),
+        }];
+
+        let mapping = UserMapping::build(&users, &previous, &HashMap::new());
+
+        assert_eq!(mapping.get("U1").unwrap().new_name, "alice2");
+        assert_eq!(mapping.get("U2").unwrap().new_name, "alice");
+    }
+
+    #[test]
+    fn test_mapping_merges_overridden_users() {
+        let mut users = vec![
+            user("U1", "john", false),
+            user("U2", "john.old", true),
+            user("U3", "jsmith", false),
+        ];
+        users[1].profile.email = Some("John@Example.com".to_string());
+        let overrides = HashMap::from([
+            ("U1".to_string(), "jsmith".to_string()),
+            ("john@example.com".to_string(), "jsmith".to_string()),
+        ]);
+
+        let mapping = UserMapping::build(&users, &[], &overrides);
+
+        assert_eq!(mapping.get("U3").unwrap().new_name, "jsmith2");
+        assert_eq!(mapping.canonical_id("U2"), "U1");
+
+        let mut post: SlackPost =
+            serde_json::from_value(json!({"user": "U2", "text": "cc <@U2|john.old> <@U3>"}))
+                .unwrap();
+        mapping.apply_post(&mut post);
+        assert_eq!(post.user(), Some("U1"));
+        assert_eq!(post.text(), Some("cc <@U1|john.old> <@U3>"));
+
+        // Mentions in rendered blocks are mapped too
+        let mut post: SlackPost = serde_json::from_value(json!({
+            "user": "U2",
+            "text": "hi <@U2>",
+            "blocks": [{"type": "rich_text", "elements": [
+                {"type": "rich_text_section", "elements": [
+                    {"type": "text", "text": "hi "},
+                    {"type": "user", "user_id": "U2"}
+                ]}
+            ]}]
+        }))
+        .unwrap();
+        render::render_post(&mut post, true);
+        mapping.apply_post(&mut post);
+        assert_eq!(post.text(), Some("hi <@U1>"));
+
+        // So are huddle participants
+        let mut post: SlackPost = serde_json::from_value(json!({
+            "subtype": "huddle_thread",
+            "text": "",
+            "room": {"id": "R1", "participant_history": ["U2", "U3"]}
+        }))
+        .unwrap();
+        render::render_post(&mut post, true);
+        fallbacks::apply_fallbacks(&mut post);
+        mapping.apply_post(&mut post);
+        assert_eq!(
+            post.text(),
+            Some(":telephone_receiver: Huddle with <@U1>, <@U3>")
+        );
+
+        let mut members = vec!["U2".to_string(), "U1".to_string(), "U3".to_string()];
+        mapping.apply_members(&mut members);
+        assert_eq!(members, vec!["U1", "U3"]);
+
+        mapping.apply(&mut users);
+        assert_eq!(users.len(), 2);
+    }
+
+    #[test]
+    fn test_mapping_adds_late_users() {
+        let users = vec![user("U1", "bob", false)];
+        let mut mapping = UserMapping::build(&users, &[], &HashMap::new());
+
+        assert!(mapping.add(&user("UEXT1", "Bob", false), None));
+        assert_eq!(mapping.get("UEXT1").unwrap().new_name, "bob2");
+
+        assert!(!mapping.add(&user("UEXT2", "carol", false), Some("bob")));
+        assert_eq!(mapping.canonical_id("UEXT2"), "U1");
+
+        mapping.merge("UEXT1", "UEXT2");
+        assert_eq!(mapping.canonical_id("UEXT1"), "U1");
+        assert_eq!(mapping.get("UEXT1").unwrap().new_name, "bob");
+    }
+}
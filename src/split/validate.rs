@@ -0,0 +1,150 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::inspect::OutputFormat;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    // File cannot be parsed
+    Json,
+    // Required file or directory is absent
+    Missing,
+    // Directory not listed in channels/groups/mpims/dms.json
+    Orphan,
+    // Unknown user or channel referenced
+    Reference,
+    Duplicate,
+    // Same id under another name in a later export
+    Rename,
+    // Unexpected file name or content layout
+    Layout,
+    // Content differs between source and output
+    Mismatch,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Problem {
+    pub severity: Severity,
+    pub category: Category,
+    pub path: String,
+    pub message: String,
+}
+
+// Problems found by `validate` and `verify`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Problems {
+    pub problems: Vec<Problem>,
+}
+
+impl Problems {
+    pub fn push(&mut self, severity: Severity, category: Category, path: &str, message: String) {
+        self.problems.push(Problem {
+            severity,
+            category,
+            path: path.to_string(),
+            message,
+        });
+    }
+
+    pub fn error(&mut self, category: Category, path: &str, message: String) {
+        self.push(Severity::Error, category, path, message);
+    }
+
+    pub fn warning(&mut self, category: Category, path: &str, message: String) {
+        self.push(Severity::Warning, category, path, message);
+    }
+
+    pub fn errors(&self) -> usize {
+        self.problems
+            .iter()
+            .filter(|p| p.severity == Severity::Error)
+            .count()
+    }
+
+    pub fn render(&mut self, format: OutputFormat) -> Result<String> {
+        self.problems.sort_by(|a, b| {
+            (b.severity, a.category, &a.path).cmp(&(a.severity, b.category, &b.path))
+        });
+        if format == OutputFormat::Json {
+            return Ok(serde_json::to_string_pretty(self)?);
+        }
+
+        let mut out = String::new();
+        for p in self.problems.iter() {
+            let severity = serde_json::to_value(p.severity).unwrap_or_default();
+            let category = serde_json::to_value(p.category).unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "{:<7}  {:<9}  {}: {}",
+                severity.as_str().unwrap_or_default().to_uppercase(),
+                category.as_str().unwrap_or_default(),
+                p.path,
+                p.message
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{} errors, {} warnings",
+            self.errors(),
+            self.problems.len() - self.errors()
+        );
+        Ok(out)
+    }
+}
+
+// Day files are named by date: `YYYY-MM-DD.json`
+pub fn is_day_file(filename: &str) -> bool {
+    let name = filename.rsplit('/').next().unwrap_or_default();
+    let Some(date) = name.strip_suffix(".json") else {
+        return false;
+    };
+    let parts: Vec<&str> = date.split('-').collect();
+    parts.len() == 3
+        && [4, 2, 2]
+            .iter()
+            .zip(parts.iter())
+            .all(|(len, part)| part.len() == *len && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_problems_render() {
+        assert!(is_day_file("general/2020-09-13.json"));
+        assert!(!is_day_file("general/notes.json"));
+        assert!(!is_day_file("general/2020-9-13.json"));
+
+        let mut problems = Problems::default();
+        problems.warning(
+            Category::Missing,
+            "D9",
+            "direct has no directory".to_string(),
+        );
+        problems.error(
+            Category::Orphan,
+            "ghost",
+            "directory is not listed".to_string(),
+        );
+        assert_eq!(problems.errors(), 1);
+
+        let table = problems.render(OutputFormat::Table).unwrap();
+        assert_eq!(
+            table,
+            "ERROR    orphan     ghost: directory is not listed\n\
+             WARNING  missing    D9: direct has no directory\n\
+             1 errors, 1 warnings\n"
+        );
+    }
+}